@@ -1,6 +1,7 @@
 import { p256 } from '@noble/curves/p256';
 import { hkdf } from '@noble/hashes/hkdf';
 import { sha256 } from '@noble/hashes/sha256';
+import { randomBytes } from '@noble/hashes/utils';
 import {
   DeployParams,
   ExecuteParams,
@@ -12,15 +13,33 @@ import {
   JsonRpcRequest,
   DeploymentCostParams,
   ExecutionCostParams,
+  TransactionOutput,
+  CostOutput,
 } from './types';
 import { bytesToHex, hexToBytes } from '@noble/curves/abstract/utils';
 
 export * from './types';
+
+/** request encryption this client uses */
+const ENVELOPE_VERSION = 1;
+/** allowed clock difference for the discovery signature, in seconds */
+const MAX_DISCOVERY_AGE = 300;
+
+export interface ConnectOptions {
+  /**
+   * accept an unsigned discovery, from servers before 0.0.9 that cant sign it.
+   * Anyone between client and server can then fake the discovery
+   */
+  allowUnsignedDiscovery?: boolean;
+}
+
 export class Client {
   privateKey: Uint8Array;
   publicKey: Uint8Array;
   serverurl: string;
   serverPubKey: Uint8Array;
+  /** ask for transactions and costs as plain strings, like servers before typed results */
+  legacyResults = false;
 
   private constructor(
     privateKey: Uint8Array,
@@ -35,7 +54,8 @@ export class Client {
   }
 
   public static async new(
-    serverurl: URL
+    serverurl: URL,
+    options: ConnectOptions = {}
   ) {
     let privateKey = p256.utils.randomPrivateKey();
     let publicKey = p256.getPublicKey(privateKey);
@@ -43,15 +63,8 @@ export class Client {
 
     serverurl.username = ""
 
-    let serverConf = await Client.checkService(serverurl.toString());
-
-    if (serverConf.result.version) {
-      if (compareVersions(serverConf.result.version, '0.0.9') < 0) {
-        throw 'server version is too old: ' + serverConf.result.version;
-      }
-    } else {
-      throw 'cant get server version';
-    }
+    let nonce = bytesToHex(randomBytes(16));
+    let serverConf = await Client.checkService(serverurl.toString(), nonce);
 
     let serverPubKey;
 
@@ -59,23 +72,47 @@ export class Client {
       let serverPubKeyHex = serverConf.result.pubkey;
       let serverFingerPrint = bytesToHex(sha256(hexToBytes(serverConf.result.pubkey)))
 
-      if (ExpectServerfingerPrint == serverFingerPrint) {
-        serverPubKey = hexToBytes(serverPubKeyHex);
-      } else {
+      if (ExpectServerfingerPrint != serverFingerPrint) {
         throw 'server finger print does not match';
       }
       serverPubKey = hexToBytes(serverPubKeyHex);
+    } else if (serverConf.result.capabilities?.locked) {
+      throw 'server is locked';
     } else {
       throw 'json rpc error';
     }
 
+    let discovery = verifyDiscovery(
+      serverConf.result,
+      serverPubKey,
+      nonce,
+      options.allowUnsignedDiscovery ?? false
+    );
+
+    let capabilities = discovery.capabilities;
+    if (capabilities) {
+      if (!capabilities.envelope_versions.includes(ENVELOPE_VERSION)) {
+        throw 'server does not support envelope version ' + ENVELOPE_VERSION;
+      }
+      if (capabilities.locked) {
+        throw 'server is locked';
+      }
+    } else if (discovery.version) {
+      if (compareVersions(discovery.version, '0.0.9') < 0) {
+        throw 'server version is too old: ' + discovery.version;
+      }
+    } else {
+      throw 'cant get server version';
+    }
+
     return new Client(privateKey, publicKey, serverurl.toString(), serverPubKey);
   }
 
   static async checkService(
-    serverurl: string
+    serverurl: string,
+    nonce: string
   ): Promise<JsonRpcResult<DiscoveryResult>> {
-    let resp = await fetch(serverurl + 'discovery', {
+    let resp = await fetch(serverurl + 'discovery?nonce=' + nonce, {
       method: 'GET',
       mode: 'cors',
     });
@@ -88,70 +125,70 @@ export class Client {
     return digest_hex;
   }
 
-  async deploy(params: DeployParams) {
+  async deploy(params: DeployParams): Promise<JsonRpcResult<TransactionOutput>> {
     let resp = await this.fetch({
       method: 'deploy',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
     return resp.json();
   }
 
-  async execute(params: ExecuteParams) {
+  async execute(params: ExecuteParams): Promise<JsonRpcResult<TransactionOutput>> {
     let resp = await this.fetch({
       method: 'execute',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
     return resp.json();
   }
 
-  async transfer(params: TransferParams) {
+  async transfer(params: TransferParams): Promise<JsonRpcResult<TransactionOutput>> {
     let resp = await this.fetch({
       method: 'transfer',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
     return resp.json();
   }
 
-  async join(params: JoinParams) {
+  async join(params: JoinParams): Promise<JsonRpcResult<TransactionOutput>> {
     let resp = await this.fetch({
       method: 'join',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
     return resp.json();
   }
 
-  async split(params: SplitParams) {
+  async split(params: SplitParams): Promise<JsonRpcResult<TransactionOutput>> {
     let resp = await this.fetch({
       method: 'split',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
     return resp.json();
   }
 
-  async deployment_cost(params: DeploymentCostParams) {
+  async deployment_cost(params: DeploymentCostParams): Promise<JsonRpcResult<CostOutput>> {
     let resp = await this.fetch({
       method: 'deployment_cost',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
     return resp.json();
   }
 
-  async execution_cost(params: ExecutionCostParams) {
+  async execution_cost(params: ExecutionCostParams): Promise<JsonRpcResult<CostOutput>> {
     let resp = await this.fetch({
       method: 'execution_cost',
-      params: Object.values(params),
+      params,
       jsonrpc: '2.0',
       id: 1,
     });
@@ -170,14 +207,19 @@ export class Client {
     const encodedData = new TextEncoder().encode(body_json);
     let encryptedBody = await encryptData(encodedData, derived);
 
+    let headers: Record<string, string> = {
+      'Content-Type': 'application/octet-stream',
+      'Public-Key': bytesToHex(this.publicKey),
+    };
+    if (this.legacyResults) {
+      headers['Result-Format'] = 'legacy';
+    }
+
     let resp = fetch(this.serverurl, {
       method: 'POST',
       body: encryptedBody,
       mode: 'cors',
-      headers: {
-        'Content-Type': 'application/octet-stream',
-        'Public-Key': bytesToHex(this.publicKey),
-      },
+      headers,
     });
 
     return resp;
@@ -216,6 +258,45 @@ async function encryptData(
   return result.buffer;
 }
 
+/**
+ * Checks the signature of a discovery result and returns the signed payload.
+ * An unsigned one is only taken with `allowUnsigned`, and never when it claims
+ * capabilities, which only signing servers have.
+ */
+function verifyDiscovery(
+  discovery: DiscoveryResult,
+  serverPubKey: Uint8Array,
+  nonce: string,
+  allowUnsigned: boolean
+): DiscoveryResult {
+  let signature = discovery.signature;
+  if (!signature) {
+    if (!allowUnsigned || discovery.capabilities) {
+      throw 'discovery is not signed';
+    }
+    return discovery;
+  }
+  if (signature.nonce != nonce) {
+    throw 'discovery is signed for another nonce';
+  }
+  if (Math.abs(Date.now() / 1000 - signature.timestamp) > MAX_DISCOVERY_AGE) {
+    throw 'discovery signature is too old';
+  }
+
+  let message = new TextEncoder().encode(
+    `aleo-acc-discovery-v1\n${signature.timestamp}\n${signature.nonce}\n${signature.payload}`
+  );
+  if (!p256.verify(hexToBytes(signature.signature), sha256(message), serverPubKey)) {
+    throw 'invalid discovery signature';
+  }
+
+  let signed: DiscoveryResult = JSON.parse(signature.payload);
+  if (signed.pubkey != discovery.pubkey) {
+    throw 'signed discovery has another pubkey';
+  }
+  return signed;
+}
+
 function compareVersions(version1: string, version2: string) {
   const parts1 = version1.split('.').map(Number);
   const parts2 = version2.split('.').map(Number);
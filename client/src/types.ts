@@ -57,10 +57,35 @@ export interface ExecutionCostParams {
   query?: string,
 }
 
+/** codes of the errors returned by the service, `error.data.kind` names them too */
+export enum RpcErrorCode {
+  AesDecrypt = 1234,
+  NoPublicKey = 1235,
+  Ecdh = 1236,
+  BackendPanic = 1237,
+  WorkerCrashed = 1238,
+  JobCancelled = 1239,
+  InvalidPrivateKey = 1240,
+  ProgramNotFound = 1241,
+  InsufficientBalance = 1242,
+  RecordAlreadySpent = 1243,
+  QueryUnreachable = 1244,
+  ProvingFailed = 1245,
+  Locked = 1246,
+  // 1247 and 1248 are reserved
+  Internal = 500,
+}
+
+export interface RpcError {
+  code: number;
+  message: string;
+  data?: { kind: string; detail?: string | null };
+}
+
 export interface JsonRpcResult<T> {
   jsonrpc: string;
   result: T;
-  error: {};
+  error: RpcError;
   id: number;
 }
 
@@ -71,8 +96,53 @@ export interface JsonRpcRequest<T> {
   id: 1;
 }
 
+export interface TransactionResult {
+  transaction_id: string;
+  program: string;
+  /** null for deployments */
+  function: string | null;
+  /** base and priority fee in microcredits */
+  fee: number | null;
+  transaction: any;
+}
+
+/** in microcredits */
+export interface CostResult {
+  total: number;
+  storage: number | null;
+  namespace: number | null;
+  synthesis: number | null;
+  finalize: number | null;
+}
+
+/** a plain string with legacy results on */
+export type TransactionOutput = TransactionResult | string;
+export type CostOutput = CostResult | string;
+
+export interface Capabilities {
+  envelope_versions: number[];
+  transports: string[];
+  networks: string[];
+  backend_version: { aleowrap: string; snarkvm: string };
+  locked: boolean;
+}
+
 export interface DiscoveryResult {
   version: string;
   features: string[];
-  pubkey: string;
+  /** null while the service is locked */
+  pubkey: string | null;
+  /** missing on older servers */
+  capabilities?: Capabilities;
+  /** missing on older servers and while locked */
+  signature?: DiscoverySignature;
+}
+
+export interface DiscoverySignature {
+  /** the signed result as json */
+  payload: string;
+  timestamp: number;
+  nonce: string;
+  /** hex r || s over sha256 of the discovery message */
+  signature: string;
 }
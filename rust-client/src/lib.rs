@@ -0,0 +1,403 @@
+pub mod tls;
+pub mod types;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+pub use types::*;
+
+pub const MIN_SERVER_VERSION: &str = "0.0.9";
+/// request encryption used by this client, see `Capabilities::envelope_versions`
+pub const ENVELOPE_VERSION: u32 = 1;
+
+const HEADER_PUBLIC_KEY: &str = "Public-Key";
+const HEADER_RESULT_FORMAT: &str = "Result-Format";
+/// allowed clock difference for the discovery signature
+const MAX_DISCOVERY_AGE_SECS: i64 = 300;
+
+/// Client for the acceleration service.
+///
+/// Mirrors the js client: the server pubkey from `/discovery` is checked
+/// against the fingerprint in the url, then each request is encrypted with
+/// an AES key derived from an ephemeral P-256 ECDH exchange.
+pub struct Client {
+    http: reqwest::Client,
+    server_url: Url,
+    public_key: Vec<u8>,
+    aes_key: Vec<u8>,
+    discovery: DiscoveryResult,
+    legacy_results: bool,
+}
+
+/// Options for connecting, the defaults suit current servers.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// accept an unsigned discovery, from servers before 0.0.9 that cant sign
+    /// it. Anyone between client and server can then fake the discovery
+    pub allow_unsigned_discovery: bool,
+}
+
+impl Client {
+    /// Connects to `server_url`, e.g. `http://<fingerprint>@127.0.0.1:18340`.
+    pub async fn new(server_url: &str) -> Result<Self> {
+        let http = reqwest::Client::builder().no_proxy().build()?;
+        Self::with_http_client(server_url, http).await
+    }
+
+    pub async fn with_http_client(server_url: &str, http: reqwest::Client) -> Result<Self> {
+        Self::with_options(server_url, http, ConnectOptions::default()).await
+    }
+
+    pub async fn with_options(
+        server_url: &str,
+        http: reqwest::Client,
+        options: ConnectOptions,
+    ) -> Result<Self> {
+        let mut server_url = Url::parse(server_url).context("invalid server url")?;
+        let expected_fingerprint = server_url.username().to_string();
+        if expected_fingerprint.is_empty() {
+            return Err(anyhow!("server url has no fingerprint"));
+        }
+        server_url
+            .set_username("")
+            .map_err(|_| anyhow!("invalid server url"))?;
+
+        let nonce = tls::random_nonce();
+        let discovery = Self::check_service(&http, &server_url, &nonce).await?;
+
+        let server_pubkey = discovery.pubkey.as_ref().context("server is locked")?;
+        let server_pubkey = hex::decode(server_pubkey).context("invalid server pubkey")?;
+        let fingerprint = hex::encode(tls::pubkey_to_fingerprint(&server_pubkey));
+        if fingerprint != expected_fingerprint.to_lowercase() {
+            return Err(anyhow!("server finger print does not match"));
+        }
+        let discovery = verify_discovery(
+            discovery,
+            &server_pubkey,
+            &nonce,
+            options.allow_unsigned_discovery,
+        )?;
+
+        match &discovery.capabilities {
+            Some(capabilities) => {
+                if !capabilities.envelope_versions.contains(&ENVELOPE_VERSION) {
+                    return Err(anyhow!(
+                        "server does not support envelope version {}",
+                        ENVELOPE_VERSION
+                    ));
+                }
+                if capabilities.locked {
+                    return Err(anyhow!("server is locked"));
+                }
+            }
+            None => {
+                if compare_versions(&discovery.version, MIN_SERVER_VERSION)
+                    == std::cmp::Ordering::Less
+                {
+                    return Err(anyhow!("server version is too old: {}", discovery.version));
+                }
+            }
+        }
+
+        let secret = tls::generate_p256_secret()?;
+        let public_key = tls::get_p256_pubkey(&secret);
+        let shared = tls::p256_shared_secret(&secret, &server_pubkey)?;
+        let aes_key = tls::shared_secret_to_symmetric_secret(&shared);
+
+        Ok(Self {
+            http,
+            server_url,
+            public_key,
+            aes_key,
+            discovery,
+            legacy_results: false,
+        })
+    }
+
+    async fn check_service(
+        http: &reqwest::Client,
+        server_url: &Url,
+        nonce: &str,
+    ) -> Result<DiscoveryResult> {
+        let mut url = server_url.join("discovery")?;
+        url.query_pairs_mut().append_pair("nonce", nonce);
+        let resp: JsonRpcResponse<DiscoveryResult> = http
+            .get(url)
+            .send()
+            .await
+            .context("cant reach server")?
+            .json()
+            .await
+            .context("invalid discovery response")?;
+        if let Some(err) = resp.error {
+            return Err(err.into());
+        }
+        resp.result.context("cant get server version")
+    }
+
+    /// Asks for transactions and costs as plain strings, like servers before
+    /// typed results returned them.
+    pub fn set_legacy_results(&mut self, legacy: bool) {
+        self.legacy_results = legacy;
+    }
+
+    pub fn discovery(&self) -> &DiscoveryResult {
+        &self.discovery
+    }
+
+    /// Sends an encrypted request and returns the raw json response.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": 1,
+        });
+        let encrypted = tls::aes::aes_encode(&self.aes_key, &serde_json::to_vec(&body)?)?;
+
+        let mut req = self
+            .http
+            .post(self.server_url.clone())
+            .header(HEADER_PUBLIC_KEY, hex::encode(&self.public_key))
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream");
+        if self.legacy_results {
+            req = req.header(HEADER_RESULT_FORMAT, "legacy");
+        }
+        let resp = req
+            .body(encrypted)
+            .send()
+            .await
+            .context("cant reach server")?
+            .json()
+            .await
+            .context("invalid rpc response")?;
+        Ok(resp)
+    }
+
+    /// Calls `method` and decodes its result.
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let resp = self.request(method, params).await?;
+
+        // errors raised before the rpc handler are sent as a bare error object
+        if resp.get("jsonrpc").is_none() && resp.get("code").is_some() {
+            let err: RpcError = serde_json::from_value(resp)?;
+            return Err(err.into());
+        }
+
+        let resp: JsonRpcResponse<T> = serde_json::from_value(resp)?;
+        if let Some(err) = resp.error {
+            return Err(err.into());
+        }
+        resp.result.context("rpc response has no result")
+    }
+
+    async fn call_with<P: RpcParams, T: DeserializeOwned>(&self, params: &P) -> Result<T> {
+        self.call(P::METHOD, params.to_params()).await
+    }
+
+    pub async fn deploy(&self, params: &DeployParams) -> Result<TransactionOutput> {
+        self.call_with(params).await
+    }
+
+    pub async fn execute(&self, params: &ExecuteParams) -> Result<TransactionOutput> {
+        self.call_with(params).await
+    }
+
+    pub async fn transfer(&self, params: &TransferParams) -> Result<TransactionOutput> {
+        self.call_with(params).await
+    }
+
+    pub async fn join(&self, params: &JoinParams) -> Result<TransactionOutput> {
+        self.call_with(params).await
+    }
+
+    pub async fn split(&self, params: &SplitParams) -> Result<TransactionOutput> {
+        self.call_with(params).await
+    }
+
+    pub async fn deployment_cost(&self, params: &DeploymentCostParams) -> Result<CostOutput> {
+        self.call_with(params).await
+    }
+
+    pub async fn execution_cost(&self, params: &ExecutionCostParams) -> Result<CostOutput> {
+        self.call_with(params).await
+    }
+}
+
+/// Checks the signature of `discovery` and returns the signed payload. An
+/// unsigned one is only taken with `allow_unsigned`, and never when it
+/// claims capabilities, which only signing servers have.
+fn verify_discovery(
+    mut discovery: DiscoveryResult,
+    server_pubkey: &[u8],
+    nonce: &str,
+    allow_unsigned: bool,
+) -> Result<DiscoveryResult> {
+    let signature = match discovery.signature.take() {
+        Some(v) => v,
+        None if allow_unsigned && discovery.capabilities.is_none() => return Ok(discovery),
+        None => return Err(anyhow!("discovery is not signed")),
+    };
+    if signature.nonce != nonce {
+        return Err(anyhow!("discovery is signed for another nonce"));
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    if (now - signature.timestamp).abs() > MAX_DISCOVERY_AGE_SECS {
+        return Err(anyhow!("discovery signature is too old"));
+    }
+
+    let message = tls::discovery_message(signature.timestamp, &signature.nonce, &signature.payload);
+    let bytes = hex::decode(&signature.signature).context("invalid discovery signature")?;
+    tls::p256_verify(server_pubkey, &message, &bytes).context("invalid discovery signature")?;
+
+    let signed: DiscoveryResult =
+        serde_json::from_str(&signature.payload).context("invalid signed discovery")?;
+    if signed.pubkey != discovery.pubkey {
+        return Err(anyhow!("signed discovery has another pubkey"));
+    }
+    Ok(signed)
+}
+
+/// Compares dotted numeric versions, missing parts count as 0.
+pub fn compare_versions(version1: &str, version2: &str) -> std::cmp::Ordering {
+    let parse = |v: &str| -> Vec<u64> {
+        v.split('.')
+            .map(|part| part.trim().parse().unwrap_or(0))
+            .collect()
+    };
+    let parts1 = parse(version1);
+    let parts2 = parse(version2);
+    let len = parts1.len().max(parts2.len());
+    for i in 0..len {
+        let a = parts1.get(i).copied().unwrap_or(0);
+        let b = parts2.get(i).copied().unwrap_or(0);
+        if a != b {
+            return a.cmp(&b);
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::*;
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn discovery(pubkey: &[u8], capabilities: Option<Capabilities>) -> DiscoveryResult {
+        DiscoveryResult {
+            version: "0.0.9".to_string(),
+            features: vec!["deploy".to_string(), "discovery".to_string()],
+            pubkey: Some(hex::encode(pubkey)),
+            capabilities,
+            signature: None,
+        }
+    }
+
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            envelope_versions: vec![ENVELOPE_VERSION],
+            transports: vec!["http".to_string()],
+            networks: vec!["testnet3".to_string()],
+            backend_version: BackendVersion {
+                aleowrap: "0.1.0".to_string(),
+                snarkvm: "0.16.0".to_string(),
+            },
+            locked: false,
+        }
+    }
+
+    fn sign(
+        secret: &[u8],
+        mut discovery: DiscoveryResult,
+        nonce: &str,
+        timestamp: i64,
+    ) -> DiscoveryResult {
+        let payload = serde_json::to_string(&discovery).unwrap();
+        let message = tls::discovery_message(timestamp, nonce, &payload);
+        discovery.signature = Some(DiscoverySignature {
+            signature: hex::encode(tls::p256_sign(secret, &message).unwrap()),
+            payload,
+            timestamp,
+            nonce: nonce.to_string(),
+        });
+        discovery
+    }
+
+    #[test]
+    fn signed_discovery_is_verified() {
+        let secret = tls::generate_p256_secret().unwrap();
+        let public = tls::get_p256_pubkey(&secret);
+        let nonce = tls::random_nonce();
+        let signed = sign(
+            &secret,
+            discovery(&public, Some(capabilities())),
+            &nonce,
+            now(),
+        );
+
+        let verified = verify_discovery(signed.clone(), &public, &nonce, false).unwrap();
+        assert_eq!(verified.features, ["deploy", "discovery"]);
+        assert!(verify_discovery(signed.clone(), &public, &tls::random_nonce(), false).is_err());
+        let other_key = tls::get_p256_pubkey(&tls::generate_p256_secret().unwrap());
+        assert!(verify_discovery(signed, &other_key, &nonce, false).is_err());
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let secret = tls::generate_p256_secret().unwrap();
+        let public = tls::get_p256_pubkey(&secret);
+        let nonce = tls::random_nonce();
+        let mut signed = sign(&secret, discovery(&public, None), &nonce, now());
+        let signature = signed.signature.as_mut().unwrap();
+        signature.payload = signature.payload.replace("deploy", "deplox");
+
+        let err = verify_discovery(signed, &public, &nonce, false).unwrap_err();
+        assert_eq!(err.to_string(), "invalid discovery signature");
+    }
+
+    #[test]
+    fn stale_signature_is_rejected() {
+        let secret = tls::generate_p256_secret().unwrap();
+        let public = tls::get_p256_pubkey(&secret);
+        let nonce = tls::random_nonce();
+        let timestamp = now() - MAX_DISCOVERY_AGE_SECS - 10;
+        let signed = sign(&secret, discovery(&public, None), &nonce, timestamp);
+
+        let err = verify_discovery(signed, &public, &nonce, false).unwrap_err();
+        assert_eq!(err.to_string(), "discovery signature is too old");
+    }
+
+    #[test]
+    fn missing_signature_needs_the_legacy_option() {
+        let public = tls::get_p256_pubkey(&tls::generate_p256_secret().unwrap());
+        let nonce = tls::random_nonce();
+
+        let err = verify_discovery(discovery(&public, None), &public, &nonce, false).unwrap_err();
+        assert_eq!(err.to_string(), "discovery is not signed");
+        assert!(verify_discovery(discovery(&public, None), &public, &nonce, true).is_ok());
+        // only servers that sign have capabilities
+        let with_capabilities = discovery(&public, Some(capabilities()));
+        assert!(verify_discovery(with_capabilities, &public, &nonce, true).is_err());
+    }
+
+    #[test]
+    fn versions_compare_by_number() {
+        assert_eq!(compare_versions("0.0.9", "0.0.10"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.0", "1.1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.1", "0.0.9"), Ordering::Greater);
+        assert_eq!(compare_versions("0.0.x", "0.0.0"), Ordering::Equal);
+    }
+}
@@ -7,7 +7,7 @@ use rand_core::OsRng;
 
 pub fn aes_decode(key: &[u8], cipher_text: &[u8]) -> Result<Vec<u8>> {
     let key = GenericArray::from_slice(key);
-    let cipher = Aes256Gcm::new(&key);
+    let cipher = Aes256Gcm::new(key);
     if cipher_text.len() < 12 {
         return Err(anyhow::anyhow!("cipher text too short"));
     }
@@ -21,7 +21,7 @@ pub fn aes_decode(key: &[u8], cipher_text: &[u8]) -> Result<Vec<u8>> {
 
 pub fn aes_encode(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
     let key = GenericArray::from_slice(key);
-    let cipher = Aes256Gcm::new(&key);
+    let cipher = Aes256Gcm::new(key);
     let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
     let cipher_text = cipher
         .encrypt(&nonce, plaintext)
@@ -0,0 +1,109 @@
+pub mod aes;
+
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use anyhow::Result;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::elliptic_curve::ScalarPrimitive;
+use sha2::{Digest, Sha256};
+
+pub fn p256_shared_secret(secret_key: &[u8], public_key: &[u8]) -> Result<Vec<u8>> {
+    let private_key = GenericArray::from_slice(secret_key);
+    let secret = p256::SecretKey::from_bytes(private_key)?;
+    let pubkey = p256::PublicKey::from_sec1_bytes(public_key)?;
+    let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), pubkey.as_affine());
+    Ok(shared.raw_secret_bytes().to_vec())
+}
+
+pub fn shared_secret_to_symmetric_secret(shares: &[u8]) -> Vec<u8> {
+    let hk = Hkdf::<Sha256>::new(None, shares);
+    let mut okm = [0u8; 32];
+    hk.expand(&[], &mut okm)
+        .expect("32 is a valid length for Sha256 to output");
+    okm.to_vec()
+}
+
+pub fn generate_p256_secret() -> Result<Vec<u8>> {
+    let sk = p256::SecretKey::new(ScalarPrimitive::random(&mut OsRng));
+    let sk = sk.to_bytes();
+    Ok(sk.to_vec())
+}
+
+pub fn get_p256_pubkey(sk: &[u8]) -> Vec<u8> {
+    let sk_bytes = GenericArray::from_slice(sk);
+    let sk = p256::SecretKey::from_bytes(sk_bytes).unwrap();
+    let pubkey = sk.public_key();
+    pubkey.to_sec1_bytes().to_vec()
+}
+
+pub fn pubkey_to_fingerprint(pk: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(pk);
+    let result = hasher.finalize();
+    result.to_vec()
+}
+
+/// ECDSA P-256 over the SHA-256 of `message`, as 64 bytes of r and s.
+pub fn p256_sign(secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let secret = p256::SecretKey::from_bytes(GenericArray::from_slice(secret_key))?;
+    let signature: p256::ecdsa::Signature = p256::ecdsa::SigningKey::from(secret).sign(message);
+    // js verifiers reject signatures with a high s by default
+    let signature = signature.normalize_s().unwrap_or(signature);
+    Ok(signature.to_bytes().to_vec())
+}
+
+pub fn p256_verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<()> {
+    let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)?;
+    let signature = p256::ecdsa::Signature::from_slice(signature)?;
+    key.verify(message, &signature)?;
+    Ok(())
+}
+
+/// What the server signs in a discovery response.
+pub fn discovery_message(timestamp: i64, nonce: &str, payload: &str) -> Vec<u8> {
+    format!(
+        "aleo-acc-discovery-v1\n{}\n{}\n{}",
+        timestamp, nonce, payload
+    )
+    .into_bytes()
+}
+
+/// Random hex challenge for the discovery signature.
+pub fn random_nonce() -> String {
+    let mut nonce = [0u8; 16];
+    OsRng.fill_bytes(&mut nonce);
+    hex::encode(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovery_signature_round_trip() {
+        let secret = generate_p256_secret().unwrap();
+        let public = get_p256_pubkey(&secret);
+        let nonce = random_nonce();
+        let message = discovery_message(1_700_000_000, &nonce, r#"{"version":"1"}"#);
+
+        let signature = p256_sign(&secret, &message).unwrap();
+        assert_eq!(signature.len(), 64);
+        p256_verify(&public, &message, &signature).unwrap();
+
+        let other = discovery_message(1_700_000_001, &nonce, r#"{"version":"1"}"#);
+        assert!(p256_verify(&public, &other, &signature).is_err());
+        let other_key = get_p256_pubkey(&generate_p256_secret().unwrap());
+        assert!(p256_verify(&other_key, &message, &signature).is_err());
+    }
+
+    #[test]
+    fn discovery_message_layout() {
+        assert_eq!(
+            discovery_message(5, "ab", "{}"),
+            b"aleo-acc-discovery-v1\n5\nab\n{}".to_vec()
+        );
+        assert_eq!(random_nonce().len(), 32);
+    }
+}
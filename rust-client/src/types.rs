@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeployParams {
+    pub private_key: String,
+    pub program: String,
+    pub fee_record: Option<String>,
+    pub imports: Option<HashMap<String, String>>,
+    pub priority_fee_in_microcredits: Option<u64>,
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecuteParams {
+    pub private_key: String,
+    pub program_id: String,
+    pub function: String,
+    pub inputs: Vec<String>,
+    pub record: Option<String>,
+    pub fee: Option<u64>,
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferParams {
+    pub private_key: String,
+    pub recipient: String,
+    pub amount: u64,
+    /// one of `private`, `public`, `private_to_public`, `public_to_private`
+    pub function: String,
+    pub input_record: Option<String>,
+    pub fee_record: Option<String>,
+    pub fee: Option<u64>,
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JoinParams {
+    pub private_key: String,
+    pub first_record: String,
+    pub second_record: String,
+    pub fee_record: Option<String>,
+    pub fee: Option<u64>,
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SplitParams {
+    pub private_key: String,
+    pub record: String,
+    pub amount: u64,
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeploymentCostParams {
+    pub program: String,
+    pub imports: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionCostParams {
+    pub private_key: String,
+    pub program_id: String,
+    pub function: String,
+    pub inputs: Vec<String>,
+    pub query: Option<String>,
+}
+
+/// Params in the positional order the server expects.
+pub trait RpcParams {
+    const METHOD: &'static str;
+
+    fn to_params(&self) -> Value;
+}
+
+impl RpcParams for DeployParams {
+    const METHOD: &'static str = "deploy";
+
+    fn to_params(&self) -> Value {
+        json!([
+            self.private_key,
+            self.program,
+            self.fee_record,
+            self.imports,
+            self.priority_fee_in_microcredits,
+            self.query,
+        ])
+    }
+}
+
+impl RpcParams for ExecuteParams {
+    const METHOD: &'static str = "execute";
+
+    fn to_params(&self) -> Value {
+        json!([
+            self.private_key,
+            self.program_id,
+            self.function,
+            self.inputs,
+            self.record,
+            self.fee,
+            self.query,
+        ])
+    }
+}
+
+impl RpcParams for TransferParams {
+    const METHOD: &'static str = "transfer";
+
+    fn to_params(&self) -> Value {
+        json!([
+            self.private_key,
+            self.recipient,
+            self.amount,
+            self.function,
+            self.input_record,
+            self.fee_record,
+            self.fee,
+            self.query,
+        ])
+    }
+}
+
+impl RpcParams for JoinParams {
+    const METHOD: &'static str = "join";
+
+    fn to_params(&self) -> Value {
+        json!([
+            self.private_key,
+            self.first_record,
+            self.second_record,
+            self.fee_record,
+            self.fee,
+            self.query,
+        ])
+    }
+}
+
+impl RpcParams for SplitParams {
+    const METHOD: &'static str = "split";
+
+    fn to_params(&self) -> Value {
+        json!([self.private_key, self.record, self.amount, self.query])
+    }
+}
+
+impl RpcParams for DeploymentCostParams {
+    const METHOD: &'static str = "deployment_cost";
+
+    fn to_params(&self) -> Value {
+        json!([self.program, self.imports])
+    }
+}
+
+impl RpcParams for ExecutionCostParams {
+    const METHOD: &'static str = "execution_cost";
+
+    fn to_params(&self) -> Value {
+        json!([
+            self.private_key,
+            self.program_id,
+            self.function,
+            self.inputs,
+            self.query,
+        ])
+    }
+}
+
+/// Result of the methods that create a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TransactionOutput {
+    Typed(TransactionResult),
+    /// the transaction json, with legacy results on
+    Legacy(String),
+}
+
+/// Result of the cost estimates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CostOutput {
+    Typed(CostResult),
+    /// the total, with legacy results on
+    Legacy(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionResult {
+    pub transaction_id: String,
+    pub program: String,
+    /// none for deployments
+    pub function: Option<String>,
+    /// base and priority fee in microcredits
+    pub fee: Option<u64>,
+    pub transaction: Value,
+}
+
+/// Cost in microcredits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostResult {
+    pub total: u64,
+    pub storage: Option<u64>,
+    pub namespace: Option<u64>,
+    pub synthesis: Option<u64>,
+    pub finalize: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryResult {
+    pub version: String,
+    pub features: Vec<String>,
+    /// none while the service is locked
+    pub pubkey: Option<String>,
+    /// missing on older servers
+    pub capabilities: Option<Capabilities>,
+    /// missing on older servers and while locked
+    pub signature: Option<DiscoverySignature>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoverySignature {
+    /// the signed result as json
+    pub payload: String,
+    pub timestamp: i64,
+    pub nonce: String,
+    /// hex, see `tls::discovery_message`
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub envelope_versions: Vec<u32>,
+    pub transports: Vec<String>,
+    pub networks: Vec<String>,
+    pub backend_version: BackendVersion,
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendVersion {
+    pub aleowrap: String,
+    pub snarkvm: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcResponse<T> {
+    pub jsonrpc: Option<String>,
+    pub result: Option<T>,
+    pub error: Option<RpcError>,
+    pub id: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// Name of the error in the service's catalogue, e.g. `program_not_found`.
+    pub fn kind(&self) -> Option<&str> {
+        self.data.as_ref()?.get("kind")?.as_str()
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rpc error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
@@ -1,4 +1,20 @@
-use std::{env, fs::File, io::Write, path::Path, process::Command};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    fs::File,
+    io::Write,
+    path::Path,
+    process::Command,
+};
+
+use serde_json::{json, Value};
+use syn::{
+    Expr, ExprLit, Fields, FnArg, GenericArgument, Item, ItemEnum, ItemStruct, Lit, LitStr, Meta,
+    Pat, PathArguments, ReturnType, TraitItem, Type,
+};
+
+/// files holding the `Rpc` trait and the types it returns
+const RPC_SOURCES: [&str; 2] = ["src/rpc/rpc.rs", "src/rpc/types.rs"];
 
 fn main() {
     tauri_build::build();
@@ -16,4 +32,273 @@ fn main() {
 
     let mut f = File::create(Path::new(&env::var("OUT_DIR").unwrap()).join("build_time")).unwrap();
     f.write_all(build_time.as_bytes()).unwrap();
+
+    // versions of the proving backend, advertised in discovery
+    println!("cargo:rerun-if-changed=../Cargo.lock");
+    let lock = fs::read_to_string("../Cargo.lock").unwrap_or_default();
+    for (package, var) in [
+        ("aleowrap", "ALEOWRAP_VERSION"),
+        ("snarkvm", "SNARKVM_VERSION"),
+    ] {
+        println!("cargo:rustc-env={}={}", var, locked_version(&lock, package));
+    }
+
+    let schema = rpc_schema();
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join("rpc_schema.json"),
+        serde_json::to_vec_pretty(&schema).unwrap(),
+    )
+    .unwrap();
+}
+
+/// Version of `package` in the lock file, `unknown` if it is not there.
+fn locked_version(lock: &str, package: &str) -> String {
+    let name = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line == name {
+            if let Some(version) = lines.next().and_then(|v| v.strip_prefix("version = ")) {
+                return version.trim_matches('"').to_string();
+            }
+        }
+    }
+    "unknown".to_string()
+}
+
+/// OpenRPC methods and component schemas read from the `Rpc` trait, so the
+/// document served at runtime cant drift from the code.
+fn rpc_schema() -> Value {
+    let mut items = Vec::new();
+    for source in RPC_SOURCES {
+        println!("cargo:rerun-if-changed={}", source);
+        let content = fs::read_to_string(source).expect("cant read rpc source");
+        let file = syn::parse_file(&content).expect("cant parse rpc source");
+        items.extend(file.items);
+    }
+
+    let types: HashMap<String, &Item> = items
+        .iter()
+        .filter_map(|v| match v {
+            Item::Struct(s) => Some((s.ident.to_string(), v)),
+            Item::Enum(e) => Some((e.ident.to_string(), v)),
+            _ => None,
+        })
+        .collect();
+    let rpc = items
+        .iter()
+        .find_map(|v| match v {
+            Item::Trait(t) if t.ident == "Rpc" => Some(t),
+            _ => None,
+        })
+        .expect("no Rpc trait found");
+
+    let mut refs = BTreeSet::new();
+    let mut methods = Vec::new();
+    for item in &rpc.items {
+        let TraitItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(name) = rpc_method_name(&method.attrs) else {
+            continue;
+        };
+        let mut params = Vec::new();
+        for input in &method.sig.inputs {
+            let FnArg::Typed(arg) = input else {
+                continue;
+            };
+            let Pat::Ident(ident) = arg.pat.as_ref() else {
+                panic!("unsupported param pattern in rpc method {}", name);
+            };
+            let (ty, required) = match option_inner(&arg.ty) {
+                Some(inner) => (inner, false),
+                None => (arg.ty.as_ref(), true),
+            };
+            params.push(json!({
+                "name": ident.ident.to_string(),
+                "required": required,
+                "schema": type_schema(ty, &mut refs),
+            }));
+        }
+        let result = match &method.sig.output {
+            ReturnType::Type(_, ty) => type_args(ty)
+                .first()
+                .map(|v| type_schema(v, &mut refs))
+                .unwrap_or_else(|| json!({})),
+            ReturnType::Default => json!({ "type": "null" }),
+        };
+        let mut value = json!({
+            "name": name,
+            "paramStructure": "either",
+            "params": params,
+            "result": { "name": format!("{}_result", name), "schema": result },
+        });
+        if let Some(summary) = doc_comment(&method.attrs) {
+            value["summary"] = Value::String(summary);
+        }
+        methods.push(value);
+    }
+
+    // types may refer to further types
+    let mut schemas = BTreeMap::new();
+    while let Some(name) = refs.iter().find(|v| !schemas.contains_key(*v)).cloned() {
+        let schema = match types.get(&name) {
+            Some(Item::Struct(item)) => struct_schema(item, &mut refs),
+            Some(Item::Enum(item)) => enum_schema(item, &mut refs),
+            _ => panic!("type {} used by the rpc trait not found", name),
+        };
+        schemas.insert(name, schema);
+    }
+
+    json!({ "methods": methods, "schemas": schemas })
+}
+
+/// Value of `#[rpc(name = "..")]`.
+fn rpc_method_name(attrs: &[syn::Attribute]) -> Option<String> {
+    let attr = attrs.iter().find(|v| v.path().is_ident("rpc"))?;
+    let mut name = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.input.peek(syn::Token![=]) {
+            meta.value()?.parse::<Expr>()?;
+        }
+        Ok(())
+    })
+    .expect("cant parse rpc attribute");
+    name
+}
+
+fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|v| match &v.meta {
+            Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join(" ")),
+    }
+}
+
+fn type_args(ty: &Type) -> Vec<&Type> {
+    let Type::Path(path) = ty else {
+        return vec![];
+    };
+    match path.path.segments.last().map(|v| &v.arguments) {
+        Some(PathArguments::AngleBracketed(args)) => args
+            .args
+            .iter()
+            .filter_map(|v| match v {
+                GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|v| v.ident.to_string())
+            .unwrap_or_default(),
+        _ => panic!("unsupported type in rpc trait"),
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    match type_name(ty).as_str() {
+        "Option" => type_args(ty).first().copied(),
+        _ => None,
+    }
+}
+
+/// Json schema of a rust type, structs become references into `refs`.
+fn type_schema(ty: &Type, refs: &mut BTreeSet<String>) -> Value {
+    let name = type_name(ty);
+    let args = type_args(ty);
+    match (name.as_str(), args.as_slice()) {
+        ("String", []) => json!({ "type": "string" }),
+        ("bool", []) => json!({ "type": "boolean" }),
+        ("u8" | "u16" | "u32" | "u64" | "u128" | "usize", []) => {
+            json!({ "type": "integer", "minimum": 0 })
+        }
+        ("i8" | "i16" | "i32" | "i64" | "i128" | "isize", []) => json!({ "type": "integer" }),
+        ("f32" | "f64", []) => json!({ "type": "number" }),
+        ("Value", []) => json!({}),
+        ("Option", [inner]) => json!({ "oneOf": [type_schema(inner, refs), { "type": "null" }] }),
+        ("Vec", [item]) => json!({ "type": "array", "items": type_schema(item, refs) }),
+        ("HashMap" | "BTreeMap", [_, value]) => {
+            json!({ "type": "object", "additionalProperties": type_schema(value, refs) })
+        }
+        (_, []) => {
+            refs.insert(name.clone());
+            json!({ "$ref": format!("#/components/schemas/{}", name) })
+        }
+        _ => panic!("unsupported type {} in rpc trait", name),
+    }
+}
+
+fn struct_schema(item: &ItemStruct, refs: &mut BTreeSet<String>) -> Value {
+    let mut properties = serde_json::Map::new();
+    let mut required = Vec::new();
+    for field in &item.fields {
+        let name = field
+            .ident
+            .as_ref()
+            .unwrap_or_else(|| panic!("{} must have named fields", item.ident))
+            .to_string();
+        if option_inner(&field.ty).is_none() {
+            required.push(name.clone());
+        }
+        let mut schema = type_schema(&field.ty, refs);
+        if let Some(doc) = doc_comment(&field.attrs) {
+            schema["description"] = Value::String(doc);
+        }
+        properties.insert(name, schema);
+    }
+    let mut schema = json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    });
+    if let Some(doc) = doc_comment(&item.attrs) {
+        schema["description"] = Value::String(doc);
+    }
+    schema
+}
+
+/// Untagged enums with one value per variant, as used for results that have
+/// more than one shape.
+fn enum_schema(item: &ItemEnum, refs: &mut BTreeSet<String>) -> Value {
+    let variants: Vec<Value> = item
+        .variants
+        .iter()
+        .map(|variant| {
+            let ty = match &variant.fields {
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+                _ => panic!("{}::{} must hold one value", item.ident, variant.ident),
+            };
+            let mut schema = type_schema(ty, refs);
+            if let Some(doc) = doc_comment(&variant.attrs) {
+                schema["description"] = Value::String(doc);
+            }
+            schema
+        })
+        .collect();
+    let mut schema = json!({ "oneOf": variants });
+    if let Some(doc) = doc_comment(&item.attrs) {
+        schema["description"] = Value::String(doc);
+    }
+    schema
 }
@@ -0,0 +1,445 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{config::Config, redact};
+
+const AUDIT_PREFIX: &str = "audit/";
+pub(crate) const AUDIT_HEAD_KEY: &str = "audit_head";
+/// domain separation for the head mac
+const HEAD_MAC_CONTEXT: &str = "aleo-acc-audit-head-v1";
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+lazy_static! {
+    /// serializes appends so every entry links to the one before it
+    static ref APPEND_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Error,
+}
+
+/// One rpc call. `hash` covers every other field and the previous entry's
+/// hash, so editing, removing or reordering entries breaks the chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    /// unix time in milliseconds
+    pub timestamp: i64,
+    /// sha256 fingerprint of the client public key
+    pub client: String,
+    pub method: String,
+    pub params: Value,
+    pub outcome: AuditOutcome,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub transaction_id: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub entries: u64,
+    /// first entry that does not match the chain
+    pub broken_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Last entry of the chain. `mac` is an HMAC-SHA256 of it under the server
+/// key, so the chain cant be cut or rewritten without the key.
+#[derive(Serialize, Deserialize)]
+struct AuditHead {
+    seq: u64,
+    hash: String,
+    #[serde(default)]
+    mac: Option<String>,
+}
+
+impl AuditHead {
+    fn new(seq: u64, hash: String, key: &[u8]) -> Result<Self> {
+        let mut head = Self {
+            seq,
+            hash,
+            mac: None,
+        };
+        head.mac = Some(hex::encode(head.hmac(key)?.finalize().into_bytes()));
+        Ok(head)
+    }
+
+    fn hmac(&self, key: &[u8]) -> Result<Hmac<Sha256>> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).context("invalid audit key")?;
+        mac.update(format!("{}\n{}\n{}", HEAD_MAC_CONTEXT, self.seq, self.hash).as_bytes());
+        Ok(mac)
+    }
+
+    /// False for heads written before they were signed.
+    fn verify(&self, key: &[u8]) -> Result<bool> {
+        let mac = match self.mac.as_deref().map(hex::decode) {
+            Some(Ok(v)) => v,
+            _ => return Ok(false),
+        };
+        Ok(self.hmac(key)?.verify_slice(&mac).is_ok())
+    }
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> Result<String> {
+        let mut unhashed = self.clone();
+        unhashed.hash = String::new();
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&unhashed)?);
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+fn entry_key(seq: u64) -> String {
+    // zero padded so keys sort in append order
+    format!("{}{:020}", AUDIT_PREFIX, seq)
+}
+
+/// What is known about a finished call, before it is chained.
+pub struct AuditRecord<'a> {
+    pub client: &'a str,
+    pub method: &'a str,
+    pub params: Value,
+    pub result: std::result::Result<&'a Value, &'a jsonrpc_core::Error>,
+    pub duration: Duration,
+}
+
+impl Config {
+    fn audit_head(&self) -> Result<Option<AuditHead>> {
+        let db = self.db.clone().context("cant get db")?;
+        let head = match db.get(AUDIT_HEAD_KEY)? {
+            Some(v) => Some(serde_json::from_slice(&v).context("cant parse audit head")?),
+            None => None,
+        };
+        Ok(head)
+    }
+
+    pub fn append_audit(&self, record: AuditRecord) -> Result<AuditEntry> {
+        let db = self.db.clone().context("cant get db")?;
+        let key = self.get_secret_key().context("cant sign audit head")?;
+        let _lock = APPEND_LOCK.lock().unwrap();
+
+        let head = self.audit_head()?;
+        let (seq, prev_hash) = match head {
+            Some(v) => (v.seq + 1, v.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+
+        let mut params = record.params;
+        redact::redact_value(&mut params);
+        let (outcome, error, transaction_id) = match record.result {
+            Ok(result) => (AuditOutcome::Success, None, transaction_id(result)),
+            Err(e) => (
+                AuditOutcome::Error,
+                Some(redact::redact_str(&error_text(e)).into_owned()),
+                None,
+            ),
+        };
+
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: Utc::now().timestamp_millis(),
+            client: record.client.to_string(),
+            method: record.method.to_string(),
+            params,
+            outcome,
+            error,
+            duration_ms: record.duration.as_millis() as u64,
+            transaction_id,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        let head = AuditHead::new(seq, entry.hash.clone(), &key)?;
+        let entry_value = serde_json::to_vec(&entry)?;
+        let head_value = serde_json::to_vec(&head)?;
+        // the entry and the head move together, or the chain looks cut
+        db.put_batch(&[
+            (entry_key(seq).as_str(), entry_value.as_slice()),
+            (AUDIT_HEAD_KEY, head_value.as_slice()),
+        ])?;
+        Ok(entry)
+    }
+
+    /// Head signed with `new_key`, for storing together with a new server key.
+    /// A head that does not verify with `previous_key` is left as it is.
+    pub(crate) fn resign_audit_head(
+        &self,
+        previous_key: Option<&[u8]>,
+        new_key: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let head = match self.audit_head()? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        match previous_key {
+            Some(previous_key) if head.verify(previous_key)? => {
+                let head = AuditHead::new(head.seq, head.hash, new_key)?;
+                Ok(Some(serde_json::to_vec(&head)?))
+            }
+            _ => {
+                log::warn!("audit head does not verify, not signing it with the new key");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Entries after `after`, oldest first.
+    pub fn audit_page(&self, after: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>> {
+        let db = self.db.clone().context("cant get db")?;
+        let start = entry_key(after.map_or(0, |v| v.saturating_add(1)));
+        db.scan_from(AUDIT_PREFIX, &start, limit)?
+            .into_iter()
+            .map(|(key, value)| {
+                serde_json::from_slice(&value)
+                    .with_context(|| format!("cant parse audit entry {}", key))
+            })
+            .collect()
+    }
+
+    pub fn audit_entries(&self) -> Result<Vec<AuditEntry>> {
+        let db = self.db.clone().context("cant get db")?;
+        db.scan_prefix(AUDIT_PREFIX)?
+            .into_iter()
+            .map(|(key, value)| {
+                serde_json::from_slice(&value)
+                    .with_context(|| format!("cant parse audit entry {}", key))
+            })
+            .collect()
+    }
+
+    pub fn verify_audit(&self) -> Result<AuditVerification> {
+        let db = self.db.clone().context("cant get db")?;
+        let key = self.get_secret_key().context("cant verify audit head")?;
+        let head = self.audit_head()?;
+
+        let entries = db.scan_prefix(AUDIT_PREFIX)?;
+        let count = entries.len() as u64;
+        let broken = |seq: u64, error: String| AuditVerification {
+            valid: false,
+            entries: count,
+            broken_at: Some(seq),
+            error: Some(error),
+        };
+
+        let mut prev_hash = GENESIS_HASH.to_string();
+        for (expected_seq, (key, value)) in entries.iter().enumerate() {
+            let expected_seq = expected_seq as u64;
+            let entry: AuditEntry = match serde_json::from_slice(value) {
+                Ok(v) => v,
+                Err(e) => return Ok(broken(expected_seq, format!("{} is unreadable: {}", key, e))),
+            };
+            if entry.seq != expected_seq || key != &entry_key(entry.seq) {
+                return Ok(broken(expected_seq, "entry missing or out of order".to_string()));
+            }
+            if entry.prev_hash != prev_hash {
+                return Ok(broken(entry.seq, "previous hash does not match".to_string()));
+            }
+            if entry.compute_hash()? != entry.hash {
+                return Ok(broken(entry.seq, "entry hash does not match".to_string()));
+            }
+            prev_hash = entry.hash;
+        }
+
+        match head {
+            Some(head) if !head.verify(&key)? => {
+                return Ok(broken(count, "audit head signature does not match".to_string()));
+            }
+            Some(head) if head.seq + 1 != count || head.hash != prev_hash => {
+                return Ok(broken(count, "entries were removed from the end".to_string()));
+            }
+            None if count > 0 => {
+                return Ok(broken(0, "audit head is missing".to_string()));
+            }
+            _ => {}
+        }
+
+        Ok(AuditVerification {
+            valid: true,
+            entries: count,
+            broken_at: None,
+            error: None,
+        })
+    }
+}
+
+/// Code and message of an rpc error, with the detail of catalogued errors.
+fn error_text(error: &jsonrpc_core::Error) -> String {
+    match error.data.as_ref().and_then(|v| v["detail"].as_str()) {
+        Some(detail) => format!("{}: {}: {}", error.code.code(), error.message, detail),
+        None => format!("{}: {}", error.code.code(), error.message),
+    }
+}
+
+/// Id of the transaction returned by a proving method, if any.
+fn transaction_id(result: &Value) -> Option<String> {
+    if let Some(id) = result.get("transaction_id").and_then(Value::as_str) {
+        return Some(id.to_string());
+    }
+    let transaction: Value = match result {
+        Value::String(v) => serde_json::from_str(v).ok()?,
+        v => v.clone(),
+    };
+    transaction.get("id")?.as_str().map(|v| v.to_string())
+}
+
+/// Records a finished call. Failing to audit is logged, not surfaced to the client.
+pub fn record(record: AuditRecord) {
+    let method = record.method.to_string();
+    if let Err(e) = Config::get_config().append_audit(record) {
+        log::error!("failed to write audit entry for '{}': {:#}", method, e);
+    }
+}
+
+#[tauri::command]
+pub fn get_audit_log(after: Option<u64>, limit: Option<usize>) -> Result<Vec<AuditEntry>, String> {
+    Config::get_config()
+        .audit_page(after, limit.unwrap_or(DEFAULT_PAGE_SIZE))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn verify_audit_log() -> Result<AuditVerification, String> {
+    Config::get_config()
+        .verify_audit()
+        .map_err(|e| e.to_string())
+}
+
+/// Writes the whole chain to `path`, one json entry per line.
+#[tauri::command]
+pub fn export_audit_log(path: String) -> Result<u64, String> {
+    export(&path).map_err(|e| format!("{:#}", e))
+}
+
+fn export(path: &str) -> Result<u64> {
+    let entries = Config::get_config().audit_entries()?;
+    if entries.is_empty() {
+        return Err(anyhow!("audit log is empty"));
+    }
+    let mut file = BufWriter::new(File::create(path).context("cant create export file")?);
+    for entry in &entries {
+        serde_json::to_writer(&mut file, entry)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()?;
+    Ok(entries.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::in_memory();
+        config.set_password("secret").unwrap();
+        config
+    }
+
+    fn append(config: &Config, method: &str) -> AuditEntry {
+        let result = json!("ok");
+        config
+            .append_audit(AuditRecord {
+                client: "client",
+                method,
+                params: json!([]),
+                result: Ok(&result),
+                duration: Duration::from_millis(5),
+            })
+            .unwrap()
+    }
+
+    fn seqs(entries: Vec<AuditEntry>) -> Vec<u64> {
+        entries.into_iter().map(|v| v.seq).collect()
+    }
+
+    #[test]
+    fn appended_entries_verify_and_page() {
+        let config = config();
+        for method in ["deploy", "execute", "transfer"] {
+            append(&config, method);
+        }
+
+        let verification = config.verify_audit().unwrap();
+        assert!(verification.valid, "{:?}", verification);
+        assert_eq!(verification.entries, 3);
+        assert_eq!(seqs(config.audit_page(None, 2).unwrap()), [0, 1]);
+        assert_eq!(seqs(config.audit_page(Some(0), 10).unwrap()), [1, 2]);
+        assert!(config.audit_page(Some(2), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn edited_entry_breaks_the_chain() {
+        let config = config();
+        append(&config, "deploy");
+        let mut entry = append(&config, "execute");
+        append(&config, "transfer");
+
+        entry.method = "transfer".to_string();
+        let db = config.db.clone().unwrap();
+        db.put(&entry_key(1), &serde_json::to_vec(&entry).unwrap())
+            .unwrap();
+
+        let verification = config.verify_audit().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(1));
+    }
+
+    #[test]
+    fn cutting_the_chain_needs_the_server_key() {
+        let config = config();
+        append(&config, "deploy");
+        let kept = append(&config, "execute");
+        append(&config, "transfer");
+
+        let db = config.db.clone().unwrap();
+        db.delete(&entry_key(2)).unwrap();
+        for mac in [None, Some(hex::encode([0u8; 32]))] {
+            let head = AuditHead {
+                seq: 1,
+                hash: kept.hash.clone(),
+                mac,
+            };
+            db.put(AUDIT_HEAD_KEY, &serde_json::to_vec(&head).unwrap())
+                .unwrap();
+            let verification = config.verify_audit().unwrap();
+            assert!(!verification.valid);
+            assert_eq!(verification.broken_at, Some(2));
+        }
+    }
+
+    #[test]
+    fn new_server_key_re_signs_the_head() {
+        let mut config = config();
+        append(&config, "deploy");
+
+        config.create_secret_key().unwrap();
+        assert!(config.verify_audit().unwrap().valid);
+
+        config.set_password("other").unwrap();
+        assert!(config.verify_audit().unwrap().valid);
+        append(&config, "execute");
+        assert!(config.verify_audit().unwrap().valid);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+/// Start at login through an XDG autostart entry in `~/.config/autostart`.
+pub struct XdgAutostart {
+    name: String,
+    app_path: String,
+}
+
+impl XdgAutostart {
+    pub fn new(name: &str, app_path: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            app_path: app_path.to_string(),
+        }
+    }
+
+    fn entry_path(&self) -> Result<PathBuf> {
+        let dir = dirs_next::config_dir()
+            .context("cant find config dir")?
+            .join("autostart");
+        Ok(dir.join(format!("{}.desktop", self.name)))
+    }
+
+    pub fn is_enabled(&self) -> Result<bool> {
+        let path = self.entry_path()?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        let entry = fs::read_to_string(&path).context("cant read autostart entry")?;
+        let disabled = entry.lines().any(|line| {
+            let line = line.trim();
+            line == "Hidden=true" || line == "X-GNOME-Autostart-enabled=false"
+        });
+        Ok(!disabled)
+    }
+
+    pub fn enable(&self) -> Result<()> {
+        let path = self.entry_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("cant create autostart dir")?;
+        }
+        let entry = format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Version=1.0\n\
+             Name={name}\n\
+             Comment={name} startup entry\n\
+             Exec={exec}\n\
+             StartupNotify=false\n\
+             Terminal=false\n\
+             X-GNOME-Autostart-enabled=true\n",
+            name = self.name,
+            exec = quote_exec_arg(&self.app_path),
+        );
+        fs::write(&path, entry).context("cant write autostart entry")
+    }
+
+    pub fn disable(&self) -> Result<()> {
+        let path = self.entry_path()?;
+        if path.exists() {
+            fs::remove_file(&path).context("cant remove autostart entry")?;
+        }
+        Ok(())
+    }
+}
+
+/// The AppImage when running from one, the executable otherwise.
+pub fn app_path(current_exe: &Path) -> String {
+    match env::var("APPIMAGE") {
+        Ok(v) if !v.is_empty() => v,
+        _ => current_exe.display().to_string(),
+    }
+}
+
+/// Quotes an argument for the `Exec` key as described in the desktop entry
+/// spec. The quoting escapes are applied first, then backslashes are escaped
+/// again for the string value, so a literal backslash takes four.
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted.replace('\\', "\\\\")
+}
+
+/// systemd user unit that runs the service in headless mode.
+pub fn systemd_user_unit(exe: &str, args: &[String]) -> String {
+    let mut exec_start = quote_unit_arg(exe);
+    for arg in args {
+        exec_start.push(' ');
+        exec_start.push_str(&quote_unit_arg(arg));
+    }
+    format!(
+        "[Unit]\n\
+         Description=Aleo acceleration service (headless)\n\
+         \n\
+         [Service]\n\
+         Type=simple\n\
+         ExecStart={exec_start}\n\
+         Restart=on-failure\n\
+         KillSignal=SIGTERM\n\
+         TimeoutStopSec=60\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n"
+    )
+}
+
+fn quote_unit_arg(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+/// Writes the unit to `~/.config/systemd/user` and returns its path.
+pub fn install_systemd_user_unit(name: &str, unit: &str) -> Result<PathBuf> {
+    let dir = dirs_next::config_dir()
+        .context("cant find config dir")?
+        .join("systemd")
+        .join("user");
+    fs::create_dir_all(&dir).context("cant create systemd user dir")?;
+    let path = dir.join(format!("{}.service", name));
+    fs::write(&path, unit).context("cant write systemd unit")?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_arg_escapes_backslashes_twice() {
+        assert_eq!(quote_exec_arg("/opt/a b/app"), r#""/opt/a b/app""#);
+        assert_eq!(quote_exec_arg(r"C:\x"), r#""C:\\\\x""#);
+        assert_eq!(quote_exec_arg("$HOME\"%u"), r#""\\$HOME\\"%%u""#);
+    }
+}
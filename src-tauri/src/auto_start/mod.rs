@@ -1,16 +1,32 @@
-use std::sync::Arc;
+#[cfg(target_os = "linux")]
+pub mod linux;
 
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+#[cfg(not(target_os = "linux"))]
 use auto_launch::{AutoLaunch, AutoLaunchBuilder};
 use lazy_static::lazy_static;
-use tauri::utils::platform::current_exe;
+use tauri::{utils::platform::current_exe, SystemTrayMenuItemHandle};
 
 use crate::config::consts::APP_NAME;
 
+#[cfg(not(target_os = "linux"))]
+pub type AutoStart = AutoLaunch;
+#[cfg(target_os = "linux")]
+pub type AutoStart = linux::XdgAutostart;
+
+/// what the tray checkbox shows, set by `sync_menu_item`
+static SHOWN_ENABLED: AtomicBool = AtomicBool::new(false);
+
 lazy_static! {
-    pub static ref AUTO_LAUNCH: Arc<Option<AutoLaunch>> = Arc::new(init_auto_launch(APP_NAME));
+    pub static ref AUTO_LAUNCH: Arc<Option<AutoStart>> = Arc::new(init_auto_launch(APP_NAME));
 }
 
-fn init_auto_launch(name: &str) -> Option<AutoLaunch> {
+#[cfg(not(target_os = "linux"))]
+fn init_auto_launch(name: &str) -> Option<AutoStart> {
     let mut builder = AutoLaunchBuilder::new();
     builder.set_app_name(name);
 
@@ -44,17 +60,66 @@ fn init_auto_launch(name: &str) -> Option<AutoLaunch> {
         // log::info!("auto_start path {}", &app_path);
         builder.set_app_path(&app_path);
     }
-    #[cfg(target_os = "linux")]
-    return None;
-    // if let Some(appimage) = app
-    //     .env()
-    //     .appimage
-    //     .and_then(|p| p.to_str().map(|s| s.to_string()))
-    // {
-    //     builder.set_app_path(&appimage);
-    // } else {
-    //     builder.set_app_path(&current_exe.display().to_string());
-    // }
 
     builder.build().ok()
 }
+
+#[cfg(target_os = "linux")]
+fn init_auto_launch(name: &str) -> Option<AutoStart> {
+    let current_exe = current_exe().ok()?;
+    Some(linux::XdgAutostart::new(name, &linux::app_path(&current_exe)))
+}
+
+/// Sets the tray checkbox from the start at login state on disk.
+pub fn sync_menu_item(item: &SystemTrayMenuItemHandle) {
+    let enabled = match AUTO_LAUNCH.as_ref() {
+        Some(v) => v.is_enabled(),
+        None => {
+            let _ = item.set_enabled(false);
+            return;
+        }
+    };
+    match enabled {
+        Ok(enabled) => {
+            if let Err(e) = item.set_enabled(true) {
+                log::warn!("failed to enable auto start menu item: {}", e);
+            }
+            match item.set_selected(enabled) {
+                Ok(()) => SHOWN_ENABLED.store(enabled, Ordering::Relaxed),
+                Err(e) => log::warn!("failed to set auto start menu item selection: {}", e),
+            }
+        }
+        Err(e) => {
+            log::warn!("failed to get auto start enabled state: {}", e);
+            if let Err(e) = item.set_enabled(false) {
+                log::warn!("failed to set auto start menu item to disabled: {}", e)
+            }
+        }
+    }
+}
+
+/// Sets start at login to the opposite of what the tray checkbox showed, which
+/// is what the user clicked for, then updates the checkbox to what actually
+/// happened. Some trays (libappindicator) send no event to re-sync on before
+/// the menu opens, so the checkbox may be stale: the state on disk is read
+/// first and left alone when it already is what the user asked for.
+pub fn toggle(item: &SystemTrayMenuItemHandle) {
+    if let Some(v) = AUTO_LAUNCH.as_ref() {
+        let wanted = !SHOWN_ENABLED.load(Ordering::Relaxed);
+        match v.is_enabled() {
+            Ok(enabled) if enabled == wanted => {}
+            Ok(_) if wanted => {
+                if let Err(e) = v.enable() {
+                    log::warn!("failed to enable auto start: {}", e);
+                }
+            }
+            Ok(_) => {
+                if let Err(e) = v.disable() {
+                    log::warn!("failed to disable auto start: {}", e);
+                }
+            }
+            Err(e) => log::warn!("failed to get auto start enabled state: {}", e),
+        }
+    }
+    sync_menu_item(item);
+}
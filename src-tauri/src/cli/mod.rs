@@ -0,0 +1,274 @@
+use std::{
+    env, fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use aleo_acc_client::Client;
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+
+#[cfg(target_os = "linux")]
+use crate::{auto_start::linux, config::consts::APP_NAME};
+use crate::{
+    config::{
+        self,
+        consts::{NEW_PASSWORD_ENV, PASSWORD_ENV},
+        Config,
+    },
+    diagnostics, logger, redact, rpc,
+    service::app::server_url,
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Aleo acceleration service")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// directory for the config database and other state
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    /// run the rpc server without tray or window
+    #[arg(long)]
+    pub headless: bool,
+
+    /// read the config password from this file
+    #[arg(long, global = true)]
+    pub password_file: Option<PathBuf>,
+
+    /// read the config password from the first line of stdin
+    #[arg(long, global = true, conflicts_with = "password_file")]
+    pub password_stdin: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Initialize the config with a password and a new server key
+    Init,
+    /// Change the config password, this also rotates the server key
+    SetPassword {
+        /// read the new password from this file instead of ALEO_ACC_NEW_PASSWORD
+        #[arg(long)]
+        new_password_file: Option<PathBuf>,
+    },
+    /// Check that the password unlocks the config
+    Unlock,
+    /// Print the server url
+    ServerUrl,
+    /// Generate a new server key, clients have to use the new server url
+    RotateKey,
+    /// Print the persisted logs
+    Logs {
+        /// number of lines from the end
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
+    },
+    /// Call an rpc method on a running service
+    Call {
+        method: String,
+        /// json array or object of params
+        #[arg(default_value = "[]")]
+        params: String,
+        /// server url, defaults to the one of this config
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Write a zip with logs, settings and a self-test report for bug reports
+    Diagnostics {
+        /// where to write the zip
+        #[arg(short, long, default_value = "aleo-acc-diagnostics.zip")]
+        output: PathBuf,
+    },
+    /// Run one backend call read from stdin, used by the service itself
+    #[command(hide = true)]
+    Worker,
+    /// Print a systemd user unit that runs the headless service
+    #[cfg(target_os = "linux")]
+    SystemdUnit {
+        /// write it to ~/.config/systemd/user instead of printing it
+        #[arg(long)]
+        install: bool,
+    },
+}
+
+impl Command {
+    /// Commands that never write the config, they also work while the
+    /// service holds the database.
+    pub fn reads_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Unlock
+                | Command::ServerUrl
+                | Command::Logs { .. }
+                | Command::Call { .. }
+                | Command::Diagnostics { .. }
+        )
+    }
+}
+
+impl Cli {
+    /// Password from `--password-file`, `--password-stdin` or the env var,
+    /// in that order. No password means the config is not encrypted.
+    pub fn read_password(&self) -> Result<String> {
+        if let Some(path) = &self.password_file {
+            return read_password_file(path);
+        }
+        if self.password_stdin {
+            let mut password = String::new();
+            std::io::stdin()
+                .lock()
+                .read_line(&mut password)
+                .context("cant read password from stdin")?;
+            return Ok(trim_line_ending(&password).to_string());
+        }
+        Ok(env::var(PASSWORD_ENV).unwrap_or_default())
+    }
+}
+
+fn read_password_file(path: &Path) -> Result<String> {
+    let password = fs::read_to_string(path)
+        .with_context(|| format!("cant read password file {}", path.display()))?;
+    Ok(trim_line_ending(&password).to_string())
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
+
+/// The unit passes on `--data-dir` and `--password-file`, the password
+/// itself never ends up in the unit. Without a password file the service
+/// could never unlock and would be restarted forever.
+#[cfg(target_os = "linux")]
+fn systemd_unit(cli: &Cli) -> Result<String> {
+    let password_file = cli
+        .password_file
+        .as_ref()
+        .context("the unit needs --password-file to unlock the config")?;
+    let exe = linux::app_path(&env::current_exe()?);
+    let mut args = vec!["--headless".to_string()];
+    if let Some(dir) = &cli.data_dir {
+        args.push("--data-dir".to_string());
+        args.push(fs::canonicalize(dir)?.display().to_string());
+    }
+    args.push("--password-file".to_string());
+    args.push(fs::canonicalize(password_file)?.display().to_string());
+    Ok(linux::systemd_user_unit(&exe, &args))
+}
+
+/// The config for commands that write it, which need the database to
+/// themselves.
+fn writable_config() -> Result<Config> {
+    let config = Config::get_config();
+    if config.db.is_none() {
+        return Err(anyhow!(
+            "cant open the config database, stop the running service first"
+        ));
+    }
+    Ok(config)
+}
+
+pub async fn run(cli: &Cli, command: &Command) -> Result<()> {
+    // the server url is built from the rpc settings
+    rpc::settings::init();
+    match command {
+        Command::Init => {
+            let mut config = writable_config()?;
+            if config.has_password()? {
+                return Err(anyhow!("config already initialized, use set-password"));
+            }
+            config
+                .set_password(&cli.read_password()?)
+                .context("failed to set password")?;
+            Config::set_config(config);
+            println!("{}", server_url()?);
+        }
+        Command::SetPassword { new_password_file } => {
+            writable_config()?;
+            config::unlock(&cli.read_password()?)?;
+            let new_password = match new_password_file {
+                Some(path) => read_password_file(path)?,
+                None => env::var(NEW_PASSWORD_ENV)
+                    .with_context(|| format!("{} not set", NEW_PASSWORD_ENV))?,
+            };
+            let mut config = Config::get_config();
+            config
+                .set_password(&new_password)
+                .context("failed to set password")?;
+            Config::set_config(config);
+            log::info!("password changed from cli");
+            println!("{}", server_url()?);
+        }
+        Command::Unlock => {
+            config::unlock(&cli.read_password()?)?;
+            println!("ok");
+        }
+        Command::ServerUrl => {
+            config::unlock(&cli.read_password()?)?;
+            println!("{}", server_url()?);
+        }
+        Command::RotateKey => {
+            writable_config()?;
+            config::unlock(&cli.read_password()?)?;
+            Config::get_config()
+                .create_secret_key()
+                .context("failed to rotate server key")?;
+            log::info!("server key rotated from cli");
+            println!("{}", server_url()?);
+        }
+        Command::Logs { lines } => {
+            let path = logger::log_file_path()?;
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("cant read log file {}", path.display()))?;
+            let all: Vec<&str> = content.lines().collect();
+            let mut stdout = std::io::stdout().lock();
+            for line in &all[all.len().saturating_sub(*lines)..] {
+                writeln!(stdout, "{}", line)?;
+            }
+        }
+        Command::Call {
+            method,
+            params,
+            url,
+        } => {
+            let url = match url {
+                Some(v) => v.clone(),
+                None => {
+                    config::unlock(&cli.read_password()?)?;
+                    server_url()?
+                }
+            };
+            let params: serde_json::Value =
+                serde_json::from_str(params).context("params are not valid json")?;
+            let response = Client::new(&url).await?.request(method, params).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+            if response.get("error").is_some() {
+                return Err(anyhow!("rpc method '{}' returned an error", method));
+            }
+        }
+        Command::Diagnostics { output } => {
+            redact::init();
+            // without the password the report just shows the server key as unreadable
+            if let Err(e) = config::unlock(&cli.read_password()?) {
+                eprintln!("warning: {:#}", e);
+            }
+            diagnostics::export(output)?;
+            println!("{}", output.display());
+        }
+        // started by main before the config or a logger exist
+        Command::Worker => unreachable!("worker is not a cli command"),
+        #[cfg(target_os = "linux")]
+        Command::SystemdUnit { install } => {
+            let unit = systemd_unit(cli)?;
+            if *install {
+                let path = linux::install_systemd_user_unit(APP_NAME, &unit)?;
+                println!("installed {}", path.display());
+                println!("enable it with: systemctl --user enable --now {}", APP_NAME);
+            } else {
+                print!("{}", unit);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,6 @@
-
 pub const APP_NAME:&str = "aleo-acc-service";
-pub const RPC_PORT:u16 = 18340;
\ No newline at end of file
+pub const RPC_PORT:u16 = 18340;
+pub const DATA_DIR_ENV:&str = "ALEO_ACC_DATA_DIR";
+pub const PASSWORD_ENV:&str = "ALEO_ACC_PASSWORD";
+pub const NEW_PASSWORD_ENV:&str = "ALEO_ACC_NEW_PASSWORD";
+pub const NETWORKS:[&str; 1] = ["testnet3"];
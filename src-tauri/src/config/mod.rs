@@ -1,28 +1,76 @@
 pub mod consts;
+pub mod proxy;
+pub mod storage;
 
 use std::{
     env,
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use anyhow::{anyhow, Context, Result};
 use lazy_static::lazy_static;
 use sha2::{Digest, Sha256};
 
-use crate::tls;
+use crate::{audit, logger, metrics, redact, rpc, stats, tls, worker};
+
+use self::{
+    consts::{APP_NAME, DATA_DIR_ENV},
+    proxy::{ProxyConfig, ProxyTestResult},
+    storage::{MemoryStorage, RocksDbStorage, Storage},
+};
 
 lazy_static! {
+    static ref DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG: Mutex<Config> = Mutex::new(Config::new());
     pub static ref PROGRAM_ID: Mutex<String> = Mutex::new("".to_string());
 }
 
+static READ_ONLY_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+/// Overrides the data directory. Must be called before the config is first used.
+pub fn set_data_dir(dir: PathBuf) {
+    let mut data_dir = DATA_DIR.lock().unwrap();
+    *data_dir = Some(dir);
+}
+
+/// Lets the config open the database read only when a running service holds
+/// it, for cli commands that only read. Must be called before the config is
+/// first used.
+pub fn allow_read_only() {
+    READ_ONLY_FALLBACK.store(true, Ordering::Relaxed);
+}
+
+/// Resolves the data directory from `set_data_dir`, then the
+/// `ALEO_ACC_DATA_DIR` env var, then the platform data dir.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(v) = DATA_DIR.lock().unwrap().clone() {
+        return Ok(v);
+    }
+    if let Ok(v) = env::var(DATA_DIR_ENV) {
+        if !v.is_empty() {
+            return Ok(PathBuf::from(v));
+        }
+    }
+    let dir = dirs_next::data_dir()
+        .context("cant find data_dir!")?
+        .join(APP_NAME);
+    Ok(dir)
+}
+
 #[tauri::command]
 pub async fn input_password(password: String) -> Result<(), String> {
+    unlock(password.as_str()).map_err(|e| e.to_string())
+}
+
+/// Decrypts the global config and applies the settings that need the password.
+pub fn unlock(password: &str) -> Result<()> {
     let mut config = CONFIG.lock().unwrap();
-    config
-        .decrypt_config(password.as_str())
-        .context("wrong password")
-        .map_err(|e| e.to_string())?;
+    config.decrypt_config(password).context("wrong password")?;
+    load_proxy(&config);
     Ok(())
 }
 
@@ -50,27 +98,84 @@ pub async fn try_password() -> Result<bool, String> {
     if config.password.is_some() {
         return Ok(true);
     }
-    Ok(config.decrypt_config("").is_ok())
+    let decrypted = config.decrypt_config("").is_ok();
+    if decrypted {
+        load_proxy(&config);
+    }
+    Ok(decrypted)
 }
 
 #[tauri::command]
-pub fn set_proxy(proxy: String) {
-    if let Ok(()) = Config::get_config().set_proxy(&proxy) {
-        set_proxy_env(&proxy)
-    }
+pub fn set_proxy(proxy: Option<ProxyConfig>) -> Result<(), String> {
+    let config = Config::get_config();
+    let proxy = match proxy {
+        Some(mut v) => {
+            // the ui never receives the stored password, keep it if the user is unchanged
+            if v.password.is_none() {
+                if let Ok(Some(stored)) = config.get_proxy_config() {
+                    if stored.username.is_some() && stored.username == v.username {
+                        v.password = stored.password;
+                    }
+                }
+            }
+            Some(v)
+        }
+        None => None,
+    };
+    config
+        .set_proxy_config(proxy.as_ref())
+        .map_err(|e| format!("{:#}", e))?;
+    proxy::apply_proxy(proxy);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn get_proxy() -> Result<Option<String>, String> {
+pub fn get_proxy() -> Result<Option<ProxyConfig>, String> {
     let proxy = Config::get_config()
-        .get_proxy()
+        .get_proxy_config()
         .map_err(|e| e.to_string())?;
-    Ok(proxy)
+    Ok(proxy.map(|v| v.redacted()))
+}
+
+/// Checks that `proxy` (or the saved proxy if `None`) can reach `url`.
+#[tauri::command]
+pub async fn test_proxy(
+    proxy: Option<ProxyConfig>,
+    url: Option<String>,
+) -> Result<ProxyTestResult, String> {
+    let proxy = match proxy {
+        Some(v) => v,
+        None => Config::get_config()
+            .get_proxy_config()
+            .map_err(|e| e.to_string())?
+            .ok_or("no proxy set")?,
+    };
+    let result = proxy::test_proxy_connection(&proxy, url.as_deref()).await;
+    log::info!(
+        "proxy test {}: ok={}, status={:?}, {} ms",
+        result.url,
+        result.ok,
+        result.status,
+        result.elapsed_ms
+    );
+    Ok(result)
+}
+
+/// The config password has not been entered yet.
+#[derive(Debug)]
+pub struct Locked;
+
+impl std::fmt::Display for Locked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "database not decrypted")
+    }
 }
 
+impl std::error::Error for Locked {}
+
 #[derive(Clone)]
 pub struct Config {
-    pub db: Option<Arc<rocksdb::DB>>,
+    pub db: Option<Arc<dyn Storage>>,
     pub password: Option<secure_string::SecureString>,
 }
 
@@ -88,12 +193,20 @@ impl Config {
                 };
             }
         };
+        Self::with_storage(Arc::new(db))
+    }
+
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
         Self {
-            db: Some(Arc::new(db)),
+            db: Some(storage),
             password: None,
         }
     }
 
+    pub fn in_memory() -> Self {
+        Self::with_storage(Arc::new(MemoryStorage::new()))
+    }
+
     pub fn decrypt_config(&mut self, password: &str) -> Result<()> {
         let db = self.db.clone().context("cant get db")?;
         let pass_test = db.get(PASSWORD_TEST_KEY)?.context("password not set")?;
@@ -118,11 +231,20 @@ impl Config {
 
     pub fn set_password(&mut self, password: &str) -> Result<()> {
         let db = self.db.clone().context("cant get db")?;
+        // proxy credentials are sealed with the old password and must be re-sealed
+        let proxy = match self.password {
+            Some(_) => self
+                .get_proxy_config()
+                .context("cant read proxy credentials")?,
+            None => None,
+        };
+        let previous_key = self.get_secret_key().ok();
         self.password = Some(secure_string::SecureString::from(password.to_string()));
-        self.create_secret_key().context("create server secret")?;
+        self.replace_secret_key(previous_key.as_deref())
+            .context("create server secret")?;
         match password {
             "" => {
-                db.put(PASSWORD_TEST_KEY, PASSWORD_TEST)?;
+                db.put(PASSWORD_TEST_KEY, PASSWORD_TEST.as_bytes())?;
             }
             _ => {
                 let encrypt_key = hash(password);
@@ -130,13 +252,17 @@ impl Config {
                 db.put(PASSWORD_TEST_KEY, &encrypted)?;
             }
         };
+        if let Some(proxy) = proxy {
+            self.set_proxy_config(Some(&proxy))
+                .context("re-encrypt proxy credentials")?;
+        }
 
         Ok(())
     }
 
     pub fn has_password(&self) -> Result<bool> {
         let db = self.db.clone().context("cant get db")?;
-        Ok(db.key_may_exist(PASSWORD_TEST_KEY))
+        db.contains(PASSWORD_TEST_KEY)
     }
 
     pub fn get_config() -> Config {
@@ -144,73 +270,72 @@ impl Config {
         config.clone()
     }
 
-    fn create_db() -> Result<rocksdb::DB> {
-        let dbdir = dirs_next::data_dir()
-            .context("cant find data_dir!")?
-            .join("aleo-acc-service");
-        let mut opts = rocksdb::Options::default();
-        opts.create_if_missing(true);
-        let db = rocksdb::DB::open(&opts, dbdir).context("cant open db")?;
-        Ok(db)
+    /// Replaces the global config, e.g. with one backed by `MemoryStorage`.
+    pub fn set_config(new_config: Config) {
+        let mut config = CONFIG.lock().unwrap();
+        *config = new_config;
+    }
+
+    fn create_db() -> Result<RocksDbStorage> {
+        // the database lives directly in the data dir to stay compatible with
+        // installs that predate the configurable data dir
+        let dbdir = data_dir()?;
+        match RocksDbStorage::open(&dbdir) {
+            Err(e) if READ_ONLY_FALLBACK.load(Ordering::Relaxed) && dbdir.exists() => {
+                log::debug!("{:#}, opening read only", e);
+                RocksDbStorage::open_read_only(&dbdir)
+            }
+            res => res,
+        }
     }
 
     pub fn create_secret_key(&self) -> Result<Vec<u8>> {
+        self.replace_secret_key(self.get_secret_key().ok().as_deref())
+    }
+
+    /// Stores a new server key. The audit head is signed with the server key,
+    /// so it is re-signed in the same write.
+    fn replace_secret_key(&self, previous_key: Option<&[u8]>) -> Result<Vec<u8>> {
         let db = self.db.clone().context("cant get db")?;
         let secret_key = tls::generate_p256_secret().context("generate secret")?;
 
-        match &self.password {
-            Some(v) => {
-                if v.unsecure() == "" {
-                    db.put("secret_key", &secret_key)
-                        .context("cant write to db")?;
-                } else {
-                    let encrypt_key = hash(v.unsecure());
-                    let encrypted = tls::aes::aes_encode(&encrypt_key, &secret_key)?;
-                    db.put("secret_key", &encrypted)
-                        .context("cant write to db")?;
-                }
-            }
-
-            None => {
-                return Err(anyhow!("no password set!"));
-            }
+        let sealed = self.seal(&secret_key)?;
+        let head = self.resign_audit_head(previous_key, &secret_key)?;
+        let mut entries = vec![("secret_key", sealed.as_slice())];
+        if let Some(head) = &head {
+            entries.push((audit::AUDIT_HEAD_KEY, head.as_slice()));
         }
+        db.put_batch(&entries)?;
 
         Ok(secret_key)
     }
 
     pub fn get_secret_key(&self) -> Result<Vec<u8>> {
         let db = self.db.clone().context("cant get db")?;
-        let value = db
-            .get("secret_key")
-            .context("cant read db")?
-            .context("secret not set!")?;
-
-        match self
-            .password
-            .clone()
-            .context("database not decrypted")?
-            .unsecure()
-        {
-            "" => return Ok(value),
-            _ => {
-                let encrypt_key = hash(self.password.clone().context("no password")?.unsecure());
-                let decrypted = tls::aes::aes_decode(&encrypt_key, &value)?;
-                return Ok(decrypted);
-            }
-        }
+        let value = db.get("secret_key")?.context("secret not set!")?;
+
+        self.unseal(&value)
     }
 
-    pub fn set_proxy(&self, proxy: &str) -> Result<()> {
-        let db = self.db.clone().context("cant get db")?;
-        db.put("proxy", proxy).context("cant write to db")
+    /// Encrypts `value` with the config password. An empty password stores it as is.
+    pub fn seal(&self, value: &[u8]) -> Result<Vec<u8>> {
+        match self.password.as_ref().ok_or(Locked)?.unsecure() {
+            "" => Ok(value.to_vec()),
+            password => {
+                let encrypt_key = hash(password);
+                tls::aes::aes_encode(&encrypt_key, value)
+            }
+        }
     }
 
-    pub fn get_proxy(&self) -> Result<Option<String>> {
-        let db = self.db.clone().context("cant get db")?;
-        match db.get("proxy").context("cant read db")? {
-            Some(v) => Ok(Some(String::from_utf8(v).unwrap_or_default())),
-            None => Ok(None),
+    /// Reverses `seal`.
+    pub fn unseal(&self, value: &[u8]) -> Result<Vec<u8>> {
+        match self.password.as_ref().ok_or(Locked)?.unsecure() {
+            "" => Ok(value.to_vec()),
+            password => {
+                let encrypt_key = hash(password);
+                tls::aes::aes_decode(&encrypt_key, value)
+            }
         }
     }
 }
@@ -222,17 +347,64 @@ pub fn hash(str: &str) -> Vec<u8> {
     result.to_vec()
 }
 
-pub async fn init() -> Result<()> {
-    if let Ok(proxy) = Config::get_config().get_proxy() {
-        if proxy.is_some() {
-            set_proxy_env(proxy.unwrap().as_str());
-        }
-    }
+pub fn init() -> Result<()> {
+    logger::init();
+    load_proxy(&Config::get_config());
+    redact::init();
+    worker::init();
+    metrics::init();
+    stats::init();
+    rpc::settings::init();
     Ok(())
 }
 
-fn set_proxy_env(proxy: &str) {
-    env::set_var("http_proxy", &proxy);
-    env::set_var("https_proxy", &proxy);
-    env::set_var("all_proxy", &proxy);
+/// Applies the saved proxy. Called again after unlocking so credentials are picked up.
+fn load_proxy(config: &Config) {
+    match config.get_proxy_config() {
+        Ok(proxy) => proxy::apply_proxy(proxy),
+        Err(e) => log::warn!("failed to load proxy config: {:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_unlocks_only_with_the_same_password() {
+        let mut config = Config::in_memory();
+        assert!(!config.has_password().unwrap());
+        config.set_password("secret").unwrap();
+        assert!(config.has_password().unwrap());
+
+        let mut reopened = Config::with_storage(config.db.clone().unwrap());
+        assert!(reopened.decrypt_config("wrong").is_err());
+        assert!(reopened.decrypt_config("").is_err());
+        reopened.decrypt_config("secret").unwrap();
+        assert_eq!(
+            reopened.get_secret_key().unwrap(),
+            config.get_secret_key().unwrap()
+        );
+    }
+
+    #[test]
+    fn seal_needs_the_password() {
+        let mut config = Config::in_memory();
+        assert!(config.seal(b"value").unwrap_err().is::<Locked>());
+
+        config.set_password("secret").unwrap();
+        let sealed = config.seal(b"value").unwrap();
+        assert_ne!(sealed, b"value");
+        assert_eq!(config.unseal(&sealed).unwrap(), b"value");
+    }
+
+    #[test]
+    fn empty_password_stores_values_as_is() {
+        let mut config = Config::in_memory();
+        config.set_password("").unwrap();
+        assert_eq!(config.seal(b"value").unwrap(), b"value");
+
+        let mut reopened = Config::with_storage(config.db.clone().unwrap());
+        reopened.decrypt_config("").unwrap();
+    }
 }
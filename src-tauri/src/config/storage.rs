@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, path::Path, sync::RwLock};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Key-value backend behind `Config`.
+pub trait Storage: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()>;
+
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Writes all entries or none of them.
+    fn put_batch(&self, entries: &[(&str, &[u8])]) -> Result<()>;
+
+    /// Up to `limit` entries whose key starts with `prefix`, from the first
+    /// key at or after `start`, ordered by key.
+    fn scan_from(&self, prefix: &str, start: &str, limit: usize) -> Result<Vec<(String, Vec<u8>)>>;
+
+    /// All entries whose key starts with `prefix`, ordered by key.
+    fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        self.scan_from(prefix, prefix, usize::MAX)
+    }
+
+    fn contains(&self, key: &str) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+}
+
+pub struct RocksDbStorage {
+    db: rocksdb::DB,
+    read_only: bool,
+}
+
+impl RocksDbStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        let db = rocksdb::DB::open(&opts, path).context("cant open db")?;
+        Ok(Self {
+            db,
+            read_only: false,
+        })
+    }
+
+    /// Opens without taking the db lock, so it works next to a running
+    /// service. Writes made after opening are not seen.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let opts = rocksdb::Options::default();
+        let db = rocksdb::DB::open_for_read_only(&opts, path, false)
+            .context("cant open db read only")?;
+        Ok(Self {
+            db,
+            read_only: true,
+        })
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(anyhow!(
+                "db is opened read only, stop the running service first"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Storage for RocksDbStorage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.db.get(key).context("cant read db")
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.check_writable()?;
+        self.db.put(key, value).context("cant write to db")
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.check_writable()?;
+        self.db.delete(key).context("cant write to db")
+    }
+
+    fn put_batch(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        self.check_writable()?;
+        let mut batch = rocksdb::WriteBatch::default();
+        for (key, value) in entries {
+            batch.put(key, value);
+        }
+        self.db.write(batch).context("cant write to db")
+    }
+
+    fn scan_from(&self, prefix: &str, start: &str, limit: usize) -> Result<Vec<(String, Vec<u8>)>> {
+        let start = start.max(prefix);
+        let mode = rocksdb::IteratorMode::From(start.as_bytes(), rocksdb::Direction::Forward);
+        let mut entries = Vec::new();
+        // no prefix extractor is configured, so stop at the first key past the prefix
+        for item in self.db.iterator(mode) {
+            if entries.len() == limit {
+                break;
+            }
+            let (key, value) = item.context("cant read db")?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            entries.push((String::from_utf8_lossy(&key).to_string(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+}
+
+/// Storage that lives only as long as the process, used for tests and
+/// throwaway instances.
+#[derive(Default)]
+pub struct MemoryStorage {
+    map: RwLock<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let map = self.map.read().unwrap();
+        Ok(map.get(key).cloned())
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let mut map = self.map.write().unwrap();
+        map.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let mut map = self.map.write().unwrap();
+        map.remove(key);
+        Ok(())
+    }
+
+    fn put_batch(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        let mut map = self.map.write().unwrap();
+        for (key, value) in entries {
+            map.insert(key.to_string(), value.to_vec());
+        }
+        Ok(())
+    }
+
+    fn scan_from(&self, prefix: &str, start: &str, limit: usize) -> Result<Vec<(String, Vec<u8>)>> {
+        let map = self.map.read().unwrap();
+        let entries = map
+            .range(start.max(prefix).to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_scan_prefix_stops_at_prefix_end() {
+        let storage = MemoryStorage::new();
+        storage.put("audit/2", b"b").unwrap();
+        storage.put("audit/1", b"a").unwrap();
+        storage.put("auditx", b"x").unwrap();
+        storage.put("aud", b"y").unwrap();
+
+        let entries = storage.scan_prefix("audit/").unwrap();
+        let keys: Vec<&str> = entries.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["audit/1", "audit/2"]);
+    }
+
+    #[test]
+    fn memory_delete_and_contains() {
+        let storage = MemoryStorage::new();
+        storage.put("key", b"value").unwrap();
+        assert!(storage.contains("key").unwrap());
+        assert_eq!(storage.get("key").unwrap().as_deref(), Some(&b"value"[..]));
+
+        storage.delete("key").unwrap();
+        assert!(!storage.contains("key").unwrap());
+        assert_eq!(storage.get("key").unwrap(), None);
+    }
+
+    #[test]
+    fn memory_scan_from_seeks_and_limits() {
+        let storage = MemoryStorage::new();
+        storage
+            .put_batch(&[
+                ("audit/1", &b"a"[..]),
+                ("audit/2", &b"b"[..]),
+                ("audit/3", &b"c"[..]),
+                ("b", &b"x"[..]),
+            ])
+            .unwrap();
+
+        let keys = |entries: Vec<(String, Vec<u8>)>| -> Vec<String> {
+            entries.into_iter().map(|(k, _)| k).collect()
+        };
+        assert_eq!(
+            keys(storage.scan_from("audit/", "audit/2", 10).unwrap()),
+            ["audit/2", "audit/3"]
+        );
+        assert_eq!(
+            keys(storage.scan_from("audit/", "a", 1).unwrap()),
+            ["audit/1"]
+        );
+        assert!(storage
+            .scan_from("audit/", "audit/4", 10)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    net::TcpStream,
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{
+    audit::AuditOutcome,
+    config::{self, Config},
+    logger::{self, LogQuery},
+    os, redact, rpc,
+    service::{app, job},
+};
+
+/// lines taken from the end of the log file
+const LOG_FILE_LINES: usize = 2000;
+/// audit entries summarized, newest first
+const AUDIT_ENTRIES: usize = 200;
+
+#[derive(Debug, Serialize)]
+pub struct SelfTestCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl SelfTestCheck {
+    fn from_result(name: &'static str, result: Result<String>) -> Self {
+        match result {
+            Ok(detail) => Self {
+                name,
+                ok: true,
+                detail,
+            },
+            Err(e) => Self {
+                name,
+                ok: false,
+                detail: redact::redact_str(&format!("{:#}", e)).into_owned(),
+            },
+        }
+    }
+}
+
+/// Quick checks of the local setup, none of them touch the network beyond localhost.
+pub fn self_test() -> Vec<SelfTestCheck> {
+    let config = Config::get_config();
+    vec![
+        SelfTestCheck::from_result("data_dir", check_data_dir()),
+        SelfTestCheck::from_result(
+            "config_db",
+            config.has_password().map(|v| match v {
+                true => "password set".to_string(),
+                false => "no password set".to_string(),
+            }),
+        ),
+        SelfTestCheck::from_result(
+            "server_key",
+            config.get_secret_key().map(|_| "readable".to_string()),
+        ),
+        SelfTestCheck::from_result("log_file", check_log_file()),
+        SelfTestCheck::from_result(
+            "audit_chain",
+            config.verify_audit().and_then(|v| match v.valid {
+                true => Ok(format!("{} entries", v.entries)),
+                false => Err(anyhow::anyhow!(
+                    "broken at {}: {}",
+                    v.broken_at.unwrap_or_default(),
+                    v.error.unwrap_or_default()
+                )),
+            }),
+        ),
+        SelfTestCheck::from_result("rpc_server", check_rpc_server()),
+        SelfTestCheck::from_result("proxy", check_proxy(&config)),
+    ]
+}
+
+fn check_data_dir() -> Result<String> {
+    let dir = config::data_dir()?;
+    let probe = dir.join(".diagnostics-probe");
+    fs::write(&probe, b"ok").with_context(|| format!("{} is not writable", dir.display()))?;
+    fs::remove_file(&probe)?;
+    Ok(dir.display().to_string())
+}
+
+fn check_log_file() -> Result<String> {
+    let path = logger::log_file_path()?;
+    let size = fs::metadata(&path)
+        .with_context(|| format!("cant read {}", path.display()))?
+        .len();
+    Ok(format!("{} ({} bytes)", path.display(), size))
+}
+
+fn check_rpc_server() -> Result<String> {
+    let address = rpc::server_address();
+    TcpStream::connect_timeout(&address, Duration::from_secs(1))
+        .with_context(|| format!("nothing listening on {}", address))?;
+    Ok(match rpc::is_running() {
+        true => format!("listening on {}", address),
+        false => format!("listening on {}, not started by this process", address),
+    })
+}
+
+fn check_proxy(config: &Config) -> Result<String> {
+    match config.get_proxy_config()? {
+        Some(proxy) => {
+            proxy.validate()?;
+            Ok(format!("{} {}:{}", proxy.scheme.as_str(), proxy.host, proxy.port))
+        }
+        None => Ok("not configured".to_string()),
+    }
+}
+
+/// Settings with every secret left out.
+fn settings() -> Value {
+    let config = Config::get_config();
+    let proxy = config.get_proxy_config().ok().flatten().map(|v| {
+        let mut proxy = v.redacted();
+        proxy.username = proxy.username.map(|_| "***".to_string());
+        proxy
+    });
+    json!({
+        "data_dir": config::data_dir().ok(),
+        "has_password": config.has_password().ok(),
+        "log": logger::get_log_settings(),
+        "redaction": redact::get_redaction_settings(),
+        "rpc": rpc::settings::settings(),
+        "proxy": proxy,
+    })
+}
+
+fn log_file_tail() -> Result<String> {
+    let path = logger::log_file_path()?;
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("cant read log file {}", path.display()))?;
+    let lines: Vec<&str> = content.lines().collect();
+    let mut tail = String::new();
+    // lines written before redaction existed may still hold secrets
+    for line in &lines[lines.len().saturating_sub(LOG_FILE_LINES)..] {
+        tail.push_str(&redact::redact_str(line));
+        tail.push('\n');
+    }
+    Ok(tail)
+}
+
+/// Recent audit entries without params or client fingerprints.
+fn audit_summary() -> Result<Value> {
+    let entries = Config::get_config().audit_entries()?;
+    let summary: Vec<Value> = entries
+        .iter()
+        .rev()
+        .take(AUDIT_ENTRIES)
+        .map(|v| {
+            json!({
+                "seq": v.seq,
+                "timestamp": v.timestamp,
+                "method": v.method,
+                "success": v.outcome == AuditOutcome::Success,
+                "error": v.error,
+                "duration_ms": v.duration_ms,
+                "transaction_id": v.transaction_id,
+            })
+        })
+        .collect();
+    Ok(Value::Array(summary))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut value = serde_json::to_value(value)?;
+    redact::redact_value(&mut value);
+    Ok(serde_json::to_vec_pretty(&value)?)
+}
+
+/// Writes a zip with build and os info, settings, logs, job history and a
+/// self-test report. Parts that cant be collected are listed in `errors.json`.
+pub fn export(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("cant create {}", path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default();
+    let mut errors = Vec::new();
+
+    let logs = logger::get_logs(Some(LogQuery {
+        limit: Some(usize::MAX),
+        ..Default::default()
+    }));
+    let mut log_records = Vec::new();
+    for record in &logs.records {
+        serde_json::to_writer(&mut log_records, record)?;
+        log_records.push(b'\n');
+    }
+
+    let parts: Vec<(&str, Result<Vec<u8>>)> = vec![
+        (
+            "manifest.json",
+            to_json(&json!({
+                "created_at": Utc::now().to_rfc3339(),
+                "version": env!("CARGO_PKG_VERSION"),
+            })),
+        ),
+        ("build_info.json", to_json(&app::get_build_info())),
+        ("os_info.json", to_json(&os::os_info())),
+        ("settings.json", to_json(&settings())),
+        ("self_test.json", to_json(&self_test())),
+        ("jobs.json", to_json(&job::job_history())),
+        ("audit_summary.json", audit_summary().and_then(|v| to_json(&v))),
+        ("logs.jsonl", Ok(log_records)),
+        ("logs.txt", log_file_tail().map(String::into_bytes)),
+    ];
+
+    for (name, content) in parts {
+        match content {
+            Ok(content) => {
+                zip.start_file(name, options)?;
+                zip.write_all(&content)?;
+            }
+            Err(e) => errors.push(json!({
+                "file": name,
+                "error": redact::redact_str(&format!("{:#}", e)),
+            })),
+        }
+    }
+    if !errors.is_empty() {
+        zip.start_file("errors.json", options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&errors)?)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn export_diagnostics(path: String) -> Result<(), String> {
+    export(Path::new(&path)).map_err(|e| format!("{:#}", e))?;
+    log::info!("diagnostics exported to {}", path);
+    Ok(())
+}
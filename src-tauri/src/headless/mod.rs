@@ -0,0 +1,66 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    cli::Cli,
+    config::{self, consts::PASSWORD_ENV, Config},
+    rpc::{shutdown_rpc_server, start_rpc_server},
+    service::app::server_url,
+    telemetry,
+};
+
+/// Runs the rpc server without the webview until SIGTERM or ctrl-c.
+pub async fn run(cli: &Cli) -> Result<()> {
+    log::info!("starting in headless mode");
+    config::init()?;
+
+    // an empty password would leave the config and the server key unencrypted
+    let password = cli.read_password()?;
+    if password.is_empty() {
+        return Err(anyhow!(
+            "headless mode needs a password, from --password-file, --password-stdin or {}",
+            PASSWORD_ENV
+        ));
+    }
+    if !Config::get_config().has_password()? {
+        log::info!("no password set, initializing config");
+        let mut config = Config::get_config();
+        config
+            .set_password(&password)
+            .context("failed to set password")?;
+        Config::set_config(config);
+    }
+    config::unlock(&password)?;
+
+    start_rpc_server()?;
+    log::info!("server url: {}", server_url()?);
+
+    wait_for_shutdown_signal().await?;
+    log::info!("shutting down");
+    shutdown_rpc_server().await;
+    telemetry::shutdown();
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).context("cant listen for SIGTERM")?;
+    tokio::select! {
+        _ = sigterm.recv() => log::info!("received SIGTERM"),
+        res = tokio::signal::ctrl_c() => {
+            res.context("cant listen for ctrl-c")?;
+            log::info!("received ctrl-c");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal() -> Result<()> {
+    tokio::signal::ctrl_c()
+        .await
+        .context("cant listen for ctrl-c")?;
+    log::info!("received ctrl-c");
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    panic::{self, PanicHookInfo},
+    sync::Mutex,
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use lazy_static::lazy_static;
+use os_pipe::PipeReader;
+
+use super::try_lock_if_panicking;
+use crate::{redact, service::job};
+
+pub const TARGET_STDOUT: &str = "stdout";
+pub const TARGET_STDERR: &str = "stderr";
+pub const TARGET_PANIC: &str = "panic";
+
+lazy_static! {
+    /// the stderr the process started with, kept so echoed log lines do not
+    /// loop back into the capture
+    static ref CONSOLE: Mutex<Option<File>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    fn target(&self) -> &'static str {
+        match self {
+            Stream::Stdout => TARGET_STDOUT,
+            Stream::Stderr => TARGET_STDERR,
+        }
+    }
+}
+
+/// Redirects stdout and stderr of the whole process, including the proving
+/// backend, into the log. Each line is tagged with the job running at the
+/// time, lines written while several jobs run are left unattributed.
+pub fn capture_stdio() {
+    for stream in [Stream::Stdout, Stream::Stderr] {
+        match redirect(stream) {
+            Ok((reader, original)) => {
+                if let (Stream::Stderr, Some(original)) = (stream, original) {
+                    *CONSOLE.lock().unwrap() = Some(original);
+                }
+                let spawned = thread::Builder::new()
+                    .name(format!("capture {}", stream.target()))
+                    .spawn(move || forward_lines(reader, stream.target()));
+                if let Err(e) = spawned {
+                    log::error!("cant start {} capture: {}", stream.target(), e);
+                }
+            }
+            Err(e) => log::error!("cant capture {}: {:#}", stream.target(), e),
+        }
+    }
+}
+
+fn forward_lines(reader: PipeReader, target: &'static str) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                // the backend is not guaranteed to write valid utf-8
+                let text = String::from_utf8_lossy(&line);
+                let text = text.trim_end_matches(['\n', '\r']);
+                if text.is_empty() {
+                    continue;
+                }
+                let _scope = job::active_job_id().map(|id| job::enter_job(&id));
+                log::info!(target: target, "{}", text);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                log::error!("{} capture stopped: {}", target, e);
+                break;
+            }
+        }
+    }
+}
+
+/// Writes a line to the original stderr, bypassing the capture.
+pub fn write_console(line: &str) {
+    let mut console = try_lock_if_panicking(&CONSOLE);
+    match console.as_mut().and_then(|v| v.as_mut()) {
+        Some(file) => {
+            let _ = writeln!(file, "{}", line);
+        }
+        None => eprintln!("{}", line),
+    }
+}
+
+#[cfg(unix)]
+fn redirect(stream: Stream) -> Result<(PipeReader, Option<File>)> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let fd = match stream {
+        Stream::Stdout => libc::STDOUT_FILENO,
+        Stream::Stderr => libc::STDERR_FILENO,
+    };
+    let (reader, writer) = os_pipe::pipe().context("cant create pipe")?;
+    // SAFETY: plain fd syscalls, `writer` stays open until after dup2
+    unsafe {
+        let original = libc::dup(fd);
+        if libc::dup2(writer.as_raw_fd(), fd) < 0 {
+            if original >= 0 {
+                libc::close(original);
+            }
+            return Err(anyhow!("dup2 failed: {}", std::io::Error::last_os_error()));
+        }
+        let original = (original >= 0).then(|| File::from_raw_fd(original));
+        Ok((reader, original))
+    }
+}
+
+#[cfg(windows)]
+fn redirect(stream: Stream) -> Result<(PipeReader, Option<File>)> {
+    use std::os::windows::io::{FromRawHandle, IntoRawHandle};
+    use windows_sys::Win32::{
+        Foundation::INVALID_HANDLE_VALUE,
+        System::Console::{GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE},
+    };
+
+    let (std_handle, fd) = match stream {
+        Stream::Stdout => (STD_OUTPUT_HANDLE, 1),
+        Stream::Stderr => (STD_ERROR_HANDLE, 2),
+    };
+    let (reader, writer) = os_pipe::pipe().context("cant create pipe")?;
+    // the write end has to outlive the process, it now backs the std handle
+    let handle = writer.into_raw_handle();
+    // SAFETY: win32 and crt calls on handles owned by this function
+    unsafe {
+        let original = GetStdHandle(std_handle);
+        if SetStdHandle(std_handle, handle as _) == 0 {
+            return Err(anyhow!(
+                "SetStdHandle failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        // keep the c runtime in sync for native code in the backend
+        let crt_fd = libc::open_osfhandle(handle as libc::intptr_t, 0);
+        if crt_fd >= 0 {
+            libc::dup2(crt_fd, fd);
+        }
+        // gui builds have no console, the handle is then null
+        let original = (original != 0 && original != INVALID_HANDLE_VALUE)
+            .then(|| File::from_raw_handle(original as _));
+        Ok((reader, original))
+    }
+}
+
+/// Hands out the original stdout and points stdout at stderr, so nothing the
+/// backend prints can end up in the middle of what is written to it.
+#[cfg(unix)]
+pub fn take_stdout() -> Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    // SAFETY: plain fd syscalls on the standard streams
+    unsafe {
+        let original = libc::dup(libc::STDOUT_FILENO);
+        if original < 0 {
+            return Err(anyhow!("dup failed: {}", std::io::Error::last_os_error()));
+        }
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            libc::close(original);
+            return Err(anyhow!("dup2 failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(File::from_raw_fd(original))
+    }
+}
+
+#[cfg(windows)]
+pub fn take_stdout() -> Result<File> {
+    use std::{mem::ManuallyDrop, os::windows::io::FromRawHandle};
+    use windows_sys::Win32::{
+        Foundation::INVALID_HANDLE_VALUE,
+        System::Console::{GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE},
+    };
+
+    // SAFETY: win32 and crt calls on the standard handles
+    unsafe {
+        let original = GetStdHandle(STD_OUTPUT_HANDLE);
+        if original == 0 || original == INVALID_HANDLE_VALUE {
+            return Err(anyhow!("no stdout"));
+        }
+        // own a duplicate, the c runtime closes the original on dup2
+        let stdout = ManuallyDrop::new(File::from_raw_handle(original as _))
+            .try_clone()
+            .context("cant duplicate stdout")?;
+        if SetStdHandle(STD_OUTPUT_HANDLE, GetStdHandle(STD_ERROR_HANDLE)) == 0 {
+            return Err(anyhow!(
+                "SetStdHandle failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        libc::dup2(2, 1);
+        Ok(stdout)
+    }
+}
+
+/// Logs panics with their location and fails the job they happened in.
+/// The default hook is skipped, it would only write to the captured stderr.
+/// The panic may hit while the log or the jobs are locked by this thread, so
+/// the hook only tries their locks and drops what it cant write.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let message = redact::redact_str(&panic_message(info)).into_owned();
+        let location = info
+            .location()
+            .map(|v| format!("{}:{}", v.file(), v.line()))
+            .unwrap_or_default();
+        let thread = thread::current();
+        // only with RUST_BACKTRACE set, like the default hook
+        let backtrace = match Backtrace::capture() {
+            v if v.status() == BacktraceStatus::Captured => format!("\n{}", v),
+            _ => String::new(),
+        };
+        log::error!(
+            target: TARGET_PANIC,
+            "thread '{}' panicked at {}: {}{}",
+            thread.name().unwrap_or("<unnamed>"),
+            location,
+            message,
+            backtrace
+        );
+        job::fail_current_job(&format!("panic: {}", message));
+    }));
+}
+
+pub fn panic_message(info: &PanicHookInfo) -> String {
+    payload_message(info.payload())
+}
+
+/// Text of a panic payload, as passed to `panic!`.
+pub fn payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(v) = payload.downcast_ref::<&str>() {
+        v.to_string()
+    } else if let Some(v) = payload.downcast_ref::<String>() {
+        v.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
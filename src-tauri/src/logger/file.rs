@@ -0,0 +1,134 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+
+use super::settings::{LogRotation, LogSettings};
+
+/// Log file that is rotated by size and time, keeping `retention` old files.
+///
+/// The active file is `<name>.log`, rotated files are renamed to
+/// `<name>.<timestamp>.log` so they sort by age.
+pub struct RotatingFile {
+    dir: PathBuf,
+    name: String,
+    file: File,
+    size: u64,
+    period: String,
+    max_size: u64,
+    rotation: LogRotation,
+    retention: usize,
+}
+
+impl RotatingFile {
+    pub fn open(dir: PathBuf, name: &str, settings: &LogSettings) -> Result<Self> {
+        fs::create_dir_all(&dir).context("cant create log dir")?;
+        let path = dir.join(format!("{}.log", name));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context("cant open log file")?;
+        let metadata = file.metadata()?;
+        let period = match metadata.modified() {
+            Ok(v) => period_of(settings.rotation, chrono::DateTime::<Local>::from(v)),
+            Err(_) => current_period(settings.rotation),
+        };
+        Ok(Self {
+            dir,
+            name: name.to_string(),
+            file,
+            size: metadata.len(),
+            period,
+            max_size: settings.max_file_size,
+            rotation: settings.rotation,
+            retention: settings.retention,
+        })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(format!("{}.log", self.name))
+    }
+
+    pub fn apply_settings(&mut self, settings: &LogSettings) {
+        self.max_size = settings.max_file_size;
+        self.rotation = settings.rotation;
+        self.retention = settings.retention;
+        self.prune();
+    }
+
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64 + 1;
+        let period = current_period(self.rotation);
+        let too_big = self.max_size > 0 && self.size > 0 && self.size + len > self.max_size;
+        if too_big || period != self.period {
+            self.rotate()?;
+            self.period = period;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+        let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f");
+        let rotated = self.dir.join(format!("{}.{}.log", self.name, stamp));
+        fs::rename(self.path(), rotated).context("cant rotate log file")?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())
+            .context("cant open log file")?;
+        self.size = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Removes the oldest rotated files beyond the retention count.
+    fn prune(&self) {
+        let mut rotated = match self.rotated_files() {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        if rotated.len() <= self.retention {
+            return;
+        }
+        rotated.sort();
+        let excess = rotated.len() - self.retention;
+        for path in rotated.into_iter().take(excess) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn rotated_files(&self) -> Result<Vec<PathBuf>> {
+        let prefix = format!("{}.", self.name);
+        let active = format!("{}.log", self.name);
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with(&prefix) && file_name.ends_with(".log") && file_name != active
+            {
+                files.push(entry.path());
+            }
+        }
+        Ok(files)
+    }
+}
+
+fn current_period(rotation: LogRotation) -> String {
+    period_of(rotation, Local::now())
+}
+
+fn period_of(rotation: LogRotation, time: chrono::DateTime<Local>) -> String {
+    match rotation {
+        LogRotation::Never => String::new(),
+        LogRotation::Hourly => time.format("%Y-%m-%d %H").to_string(),
+        LogRotation::Daily => time.format("%Y-%m-%d").to_string(),
+    }
+}
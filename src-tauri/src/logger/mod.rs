@@ -1,59 +1,350 @@
-use std::sync::Mutex;
+pub mod capture;
+pub mod file;
+pub mod settings;
+pub mod stream;
 
-use chrono::Local;
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, RwLock},
+    thread,
+};
+
+use anyhow::Result;
+use chrono::{Local, TimeZone};
 use lazy_static::lazy_static;
-use log::{Log, Metadata, Record};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{self, consts::APP_NAME, Config},
+    redact,
+    service::job,
+};
+
+use self::{file::RotatingFile, settings::LogSettings};
+
+const DEFAULT_PAGE_SIZE: usize = 200;
 
 lazy_static! {
-    static ref LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static ref LOGS: Mutex<LogBuffer> = Mutex::new(LogBuffer::default());
+    static ref SETTINGS: RwLock<LogSettings> = RwLock::new(LogSettings::default());
+    static ref LOG_FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    /// increasing sequence number, used as pagination cursor
+    pub id: u64,
+    /// unix time in milliseconds
+    pub timestamp: i64,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub job_id: Option<String>,
+}
+
+impl LogRecord {
+    pub fn format(&self) -> String {
+        let time = match Local.timestamp_millis_opt(self.timestamp) {
+            chrono::LocalResult::Single(v) => v.format("%Y-%m-%d %H:%M:%S").to_string(),
+            _ => self.timestamp.to_string(),
+        };
+        match &self.job_id {
+            Some(job_id) => format!(
+                "{} :: {} [{}] ({}) - {}",
+                time, self.level, self.target, job_id, self.message
+            ),
+            None => format!(
+                "{} :: {} [{}] - {}",
+                time, self.level, self.target, self.message
+            ),
+        }
+    }
 }
 
+#[derive(Default)]
+struct LogBuffer {
+    records: VecDeque<LogRecord>,
+    next_id: u64,
+}
+
+/// Filters for `get_logs`, all optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    /// least severe level to include
+    pub level: Option<LevelFilter>,
+    pub targets: Option<Vec<String>>,
+    pub job_id: Option<String>,
+    /// unix time in milliseconds, inclusive
+    pub since: Option<i64>,
+    /// unix time in milliseconds, exclusive
+    pub until: Option<i64>,
+    /// case insensitive text search in the message
+    pub search: Option<String>,
+    /// only records after this id, oldest first
+    pub after: Option<u64>,
+    /// only records before this id, the newest ones that fit `limit`
+    pub before: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    fn matches(&self, record: &LogRecord, search: Option<&str>) -> bool {
+        if let Some(level) = self.level {
+            if record.level > level {
+                return false;
+            }
+        }
+        if let Some(targets) = &self.targets {
+            if !targets.iter().any(|v| v == &record.target) {
+                return false;
+            }
+        }
+        if let Some(job_id) = &self.job_id {
+            if record.job_id.as_ref() != Some(job_id) {
+                return false;
+            }
+        }
+        if let Some(since) = self.since {
+            if record.timestamp < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if record.timestamp >= until {
+                return false;
+            }
+        }
+        if let Some(search) = search {
+            if !record.message.to_lowercase().contains(search) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogPage {
+    pub records: Vec<LogRecord>,
+    /// more matching records exist past the end of this page
+    pub has_more: bool,
+    /// pass as `before` to get the previous page
+    pub prev_cursor: Option<u64>,
+    /// pass as `after` to get the next page or poll for new records
+    pub next_cursor: Option<u64>,
+}
+
+impl LogBuffer {
+    fn query(&self, query: &LogQuery) -> LogPage {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+        let search = query.search.as_ref().map(|v| v.to_lowercase());
+        let search = search.as_deref();
+
+        let mut records = Vec::new();
+        let mut has_more = false;
+        match query.after {
+            Some(after) => {
+                for record in self.records.iter().filter(|v| v.id > after) {
+                    if !query.matches(record, search) {
+                        continue;
+                    }
+                    if records.len() == limit {
+                        has_more = true;
+                        break;
+                    }
+                    records.push(record.clone());
+                }
+            }
+            None => {
+                let before = query.before.unwrap_or(u64::MAX);
+                for record in self.records.iter().rev().filter(|v| v.id < before) {
+                    if !query.matches(record, search) {
+                        continue;
+                    }
+                    if records.len() == limit {
+                        has_more = true;
+                        break;
+                    }
+                    records.push(record.clone());
+                }
+                records.reverse();
+            }
+        }
+
+        let last_id = self.next_id.checked_sub(1);
+        LogPage {
+            prev_cursor: records.first().map(|v| v.id),
+            next_cursor: records.last().map(|v| v.id).or(query.after).or(last_id),
+            records,
+            has_more,
+        }
+    }
+}
+
+/// Keeps the last `max_lines` records in memory for `get_logs` and writes
+/// everything to the rotating log file in the data dir.
 struct MemoryLogger {
     max_lines: usize,
+    echo: bool,
 }
 
 impl MemoryLogger {
-    fn new(max_lines: usize) -> Self {
-        MemoryLogger { max_lines }
+    fn new(max_lines: usize, echo: bool) -> Self {
+        MemoryLogger { max_lines, echo }
     }
 }
 
 impl Log for MemoryLogger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= SETTINGS.read().unwrap().level_for(metadata.target())
     }
 
     fn log(&self, record: &Record) {
-        let current_time = Local::now();
-        let formatted_time = current_time.format("%Y-%m-%d %H:%M:%S").to_string();
-        let log_entry = format!(
-            "{} :: {} - {}",
-            formatted_time,
-            record.level(),
-            record.args()
-        );
-        // println!("{}", log_entry);
-        let mut logs = LOGS.lock().unwrap();
-        logs.push(log_entry);
-        if logs.len() > self.max_lines {
-            let excess_lines = logs.len() - self.max_lines;
-            logs.drain(0..excess_lines);
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut logs = match try_lock_if_panicking(&LOGS) {
+            Some(v) => v,
+            None => {
+                let message = redact::redact_str(&record.args().to_string()).into_owned();
+                capture::write_console(&format!("{} - {}", record.target(), message));
+                return;
+            }
+        };
+        let log_record = LogRecord {
+            id: logs.next_id,
+            timestamp: Local::now().timestamp_millis(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: redact::redact_str(&record.args().to_string()).into_owned(),
+            job_id: job::current_job_id(),
+        };
+        logs.next_id += 1;
+
+        let log_entry = log_record.format();
+        if self.echo {
+            capture::write_console(&log_entry);
+        }
+        if let Some(mut file) = try_lock_if_panicking(&LOG_FILE) {
+            if let Some(file) = file.as_mut() {
+                if let Err(e) = file.write_line(&log_entry) {
+                    capture::write_console(&format!("cant write log file: {:#}", e));
+                }
+            }
+        }
+
+        stream::publish(&log_record);
+        logs.records.push_back(log_record);
+        while logs.records.len() > self.max_lines {
+            logs.records.pop_front();
         }
     }
 
     fn flush(&self) {}
 }
 
-pub fn setup_logger() {
+/// Locks `mutex`, but only tries to while the thread panics: the panic hook
+/// logs, and the panic may have hit while this thread held the lock.
+pub(crate) fn try_lock_if_panicking<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    if thread::panicking() {
+        return mutex.try_lock().ok();
+    }
+    Some(mutex.lock().unwrap())
+}
+
+/// `echo` also writes every line to stderr, for running without the ui.
+/// Starts with the default settings, the saved ones are applied by `init`
+/// once the config is opened.
+pub fn setup_logger(echo: bool) {
     let max_lines = 1000;
-    let logger = Box::new(MemoryLogger::new(max_lines));
+    let settings = LogSettings::default();
+    match log_dir().and_then(|dir| RotatingFile::open(dir, APP_NAME, &settings)) {
+        Ok(v) => *LOG_FILE.lock().unwrap() = Some(v),
+        Err(e) => eprintln!("cant open log file: {:#}", e),
+    };
+    let max_level = settings.max_level();
+    *SETTINGS.write().unwrap() = settings;
+
+    let logger = Box::new(MemoryLogger::new(max_lines, echo));
     log::set_boxed_logger(logger).unwrap();
-    log::set_max_level(log::LevelFilter::Info);
+    log::set_max_level(max_level);
     log::info!("logger init");
 }
 
+/// Warnings and errors of cli commands, written to stderr only: the log
+/// file belongs to the service, which may be running.
+struct ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = redact::redact_str(&record.args().to_string()).into_owned();
+        capture::write_console(&format!(
+            "{} {} - {}",
+            record.level(),
+            record.target(),
+            message
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn setup_console_logger() {
+    log::set_boxed_logger(Box::new(ConsoleLogger)).unwrap();
+    log::set_max_level(LevelFilter::Warn);
+}
+
+pub fn log_dir() -> Result<PathBuf> {
+    Ok(config::data_dir()?.join("logs"))
+}
+
+pub fn log_file_path() -> Result<PathBuf> {
+    Ok(log_dir()?.join(format!("{}.log", APP_NAME)))
+}
+
+/// Applies the saved log settings, called from `config::init`.
+pub fn init() {
+    match Config::get_config().get_log_settings() {
+        Ok(settings) => apply_settings(settings),
+        Err(e) => log::warn!("failed to load log settings: {:#}", e),
+    }
+}
+
+fn apply_settings(settings: LogSettings) {
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        file.apply_settings(&settings);
+    }
+    log::set_max_level(settings.max_level());
+    *SETTINGS.write().unwrap() = settings;
+}
+
+#[tauri::command]
+pub fn get_log_settings() -> LogSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn set_log_settings(settings: LogSettings) -> Result<(), String> {
+    Config::get_config()
+        .set_log_settings(&settings)
+        .map_err(|e| e.to_string())?;
+    apply_settings(settings);
+    log::info!("log settings updated");
+    Ok(())
+}
+
 #[tauri::command]
-pub fn get_logs() -> Vec<String> {
+pub fn get_logs(query: Option<LogQuery>) -> LogPage {
     let logs = LOGS.lock().unwrap();
-    logs.to_vec()
+    logs.query(&query.unwrap_or_default())
 }
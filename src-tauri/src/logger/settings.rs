@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+pub const TARGET_RPC: &str = "rpc";
+pub const TARGET_ALEOSDK: &str = "aleosdk";
+pub const TARGET_RPC_ERROR: &str = "rpc error";
+
+const LOG_SETTINGS_KEY: &str = "log_settings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// level for targets without an entry in `targets`
+    pub level: LevelFilter,
+    /// per target levels, e.g. `rpc`, `aleosdk`, `rpc error`
+    pub targets: HashMap<String, LevelFilter>,
+    /// rotate the log file once it reaches this many bytes, 0 disables it
+    pub max_file_size: u64,
+    pub rotation: LogRotation,
+    /// number of rotated files to keep
+    pub retention: usize,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        let targets = [TARGET_RPC, TARGET_ALEOSDK, TARGET_RPC_ERROR]
+            .iter()
+            .map(|v| (v.to_string(), LevelFilter::Info))
+            .collect();
+        Self {
+            level: LevelFilter::Info,
+            targets,
+            max_file_size: 10 * 1024 * 1024,
+            rotation: LogRotation::Daily,
+            retention: 7,
+        }
+    }
+}
+
+impl LogSettings {
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets.get(target).copied().unwrap_or(self.level)
+    }
+
+    /// Most verbose level of all targets, for `log::set_max_level`.
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .values()
+            .copied()
+            .fold(self.level, |max, v| max.max(v))
+    }
+}
+
+impl Config {
+    pub fn get_log_settings(&self) -> Result<LogSettings> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(LOG_SETTINGS_KEY)? {
+            Some(v) => serde_json::from_slice(&v).context("cant parse log settings"),
+            None => Ok(LogSettings::default()),
+        }
+    }
+
+    pub fn set_log_settings(&self, settings: &LogSettings) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(LOG_SETTINGS_KEY, &serde_json::to_vec(settings)?)
+    }
+}
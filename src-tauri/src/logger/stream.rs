@@ -0,0 +1,120 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+    thread,
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::service::app::APP_HANDLE;
+
+use super::{capture, try_lock_if_panicking, LogRecord};
+
+pub const LOG_EVENT: &str = "log-records";
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(250);
+/// records kept between flushes, older ones are dropped and only counted
+const MAX_PENDING: usize = 500;
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    static ref PENDING: Mutex<Pending> = Mutex::new(Pending::default());
+}
+
+static HAS_SUBSCRIBERS: AtomicBool = AtomicBool::new(false);
+static START_FLUSHER: Once = Once::new();
+
+#[derive(Default)]
+struct Pending {
+    records: VecDeque<LogRecord>,
+    dropped: usize,
+}
+
+#[derive(Clone, Serialize)]
+struct LogBatch {
+    records: Vec<LogRecord>,
+    /// records skipped because too many were logged within one interval
+    dropped: usize,
+}
+
+/// Queues `record` for the subscribed windows. Cheap when nobody listens.
+pub fn publish(record: &LogRecord) {
+    if !HAS_SUBSCRIBERS.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut pending = match try_lock_if_panicking(&PENDING) {
+        Some(v) => v,
+        None => return,
+    };
+    if pending.records.len() >= MAX_PENDING {
+        pending.records.pop_front();
+        pending.dropped += 1;
+    }
+    pending.records.push_back(record.clone());
+}
+
+#[tauri::command]
+pub fn subscribe_logs(window: tauri::Window) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.insert(window.label().to_string());
+    HAS_SUBSCRIBERS.store(true, Ordering::Relaxed);
+    START_FLUSHER.call_once(|| {
+        thread::spawn(flush_loop);
+    });
+}
+
+#[tauri::command]
+pub fn unsubscribe_logs(window: tauri::Window) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.remove(window.label());
+    if subscribers.is_empty() {
+        HAS_SUBSCRIBERS.store(false, Ordering::Relaxed);
+        *PENDING.lock().unwrap() = Pending::default();
+    }
+}
+
+/// Emits the queued records as one batch per interval. Runs on its own
+/// thread so logging never waits on the webview.
+fn flush_loop() {
+    loop {
+        thread::sleep(FLUSH_INTERVAL);
+
+        let batch = {
+            let mut pending = PENDING.lock().unwrap();
+            if pending.records.is_empty() {
+                continue;
+            }
+            let pending = std::mem::take(&mut *pending);
+            LogBatch {
+                records: pending.records.into(),
+                dropped: pending.dropped,
+            }
+        };
+
+        let handle = match APP_HANDLE.lock().unwrap().clone() {
+            Some(v) => v,
+            None => continue,
+        };
+        let subscribers: Vec<String> = SUBSCRIBERS.lock().unwrap().iter().cloned().collect();
+        for label in subscribers {
+            let window = match handle.get_window(&label) {
+                Some(v) => v,
+                None => continue,
+            };
+            // hidden windows catch up through get_logs when shown again
+            if !window.is_visible().unwrap_or(false) {
+                continue;
+            }
+            if let Err(e) = window.emit(LOG_EVENT, batch.clone()) {
+                // not through the log or stderr, both feed back into this stream
+                capture::write_console(&format!("cant emit log event: {}", e));
+            }
+        }
+    }
+}
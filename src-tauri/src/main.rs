@@ -2,16 +2,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 #![feature(internal_output_capture)]
 
+mod audit;
 mod auto_start;
+mod cli;
 mod config;
+mod diagnostics;
+mod headless;
 mod logger;
+mod metrics;
 mod os;
+mod redact;
 mod rpc;
 mod service;
+mod stats;
+mod telemetry;
 mod tls;
+mod worker;
 
 use anyhow::Result;
-use std::{io::BufReader, process::Command};
+use clap::Parser;
+use std::process::Command;
 
 use clipboard_ext::prelude::*;
 use clipboard_ext::x11_fork::ClipboardContext;
@@ -27,11 +37,30 @@ use tauri::{
     generate_handler, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
 };
 
-use config::{get_proxy, has_password, input_password, set_password, set_proxy, try_password};
-use logger::get_logs;
+use audit::{export_audit_log, get_audit_log, verify_audit_log};
+use config::{
+    get_proxy, has_password, input_password, set_password, set_proxy, test_proxy, try_password,
+};
+use diagnostics::export_diagnostics;
+use logger::{
+    get_log_settings, get_logs, set_log_settings,
+    stream::{subscribe_logs, unsubscribe_logs},
+};
+use metrics::{get_metrics_settings, set_metrics_settings};
 use os::{is_win11, os_info};
-use rpc::{run_rpc_server, stop_rpc_server};
-use service::app::{get_build_info, get_server_url};
+use redact::{get_redaction_settings, set_redaction_settings};
+use rpc::{
+    run_rpc_server,
+    settings::{get_rpc_settings, set_rpc_settings},
+    stop_rpc_server,
+};
+use service::{
+    app::{get_build_info, get_server_url},
+    job::get_job_history,
+};
+use stats::get_stats;
+use telemetry::{get_tracing_settings, set_tracing_settings};
+use worker::{cancel_job, get_worker_settings, set_worker_settings};
 
 const MENU_ITEM_AUTO_START: &str = "auto_start";
 const MENU_ITEM_QUIT: &str = "quit";
@@ -47,20 +76,46 @@ struct Payload {
 
 #[tokio::main]
 async fn main() {
-    logger::setup_logger();
+    let cli = cli::Cli::parse();
+    if let Some(dir) = &cli.data_dir {
+        config::set_data_dir(dir.clone());
+    }
+
+    if let Some(cli::Command::Worker) = &cli.command {
+        // no logger, a worker only reports back through its pipes
+        if let Err(e) = worker::child::run() {
+            eprintln!("worker failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(command) = &cli.command {
+        if command.reads_only() {
+            config::allow_read_only();
+        }
+        logger::setup_console_logger();
+        if let Err(e) = cli::run(&cli, command).await {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    logger::setup_logger(cli.headless);
     log::info!("app started!");
 
-    #[cfg(not(target_os = "windows"))]
-    tokio::spawn(async {
-        let mut piped_stdout =
-            <capture_stdio::PipedStdout as capture_stdio::Capture>::capture().unwrap();
-        let mut buf_reader = BufReader::new(piped_stdout.get_reader());
-        loop {
-            let mut output = String::new();
-            std::io::BufRead::read_line(&mut buf_reader, &mut output).unwrap();
-            log::info!("{}", output);
+    logger::capture::capture_stdio();
+    logger::capture::install_panic_hook();
+    telemetry::init();
+
+    if cli.headless {
+        if let Err(e) = headless::run(&cli).await {
+            log::error!("headless service failed: {:#}", e);
+            std::process::exit(1);
         }
-    });
+        return;
+    }
 
     let show = CustomMenuItem::new(MENUITEM_SHOW, "show window");
     let copy_addr = CustomMenuItem::new(MENUITEM_COPY_ADDR, "copy server address");
@@ -91,14 +146,36 @@ async fn main() {
     let app = tauri::Builder::default()
         .invoke_handler(generate_handler![
             get_logs,
+            get_log_settings,
+            set_log_settings,
+            subscribe_logs,
+            unsubscribe_logs,
             stop_rpc_server,
             run_rpc_server,
             is_win11,
             os_info,
             set_proxy,
             get_proxy,
+            test_proxy,
+            get_redaction_settings,
+            set_redaction_settings,
             get_server_url,
             get_build_info,
+            get_audit_log,
+            verify_audit_log,
+            export_audit_log,
+            get_job_history,
+            cancel_job,
+            get_worker_settings,
+            set_worker_settings,
+            get_metrics_settings,
+            get_stats,
+            get_tracing_settings,
+            set_tracing_settings,
+            set_metrics_settings,
+            get_rpc_settings,
+            set_rpc_settings,
+            export_diagnostics,
             has_password,
             input_password,
             set_password,
@@ -145,6 +222,8 @@ async fn main() {
             #[cfg(any(windows, target_os = "macos"))]
             set_shadow(&window, true).unwrap();
 
+            auto_start::sync_menu_item(&app.tray_handle().get_item(MENU_ITEM_AUTO_START));
+
             Ok(())
         })
         .on_system_tray_event(|app, event| match event {
@@ -153,6 +232,7 @@ async fn main() {
                 size: _,
                 ..
             } => {
+                auto_start::sync_menu_item(&app.tray_handle().get_item(MENU_ITEM_AUTO_START));
                 let window = app.get_window("main").unwrap();
 
                 if window.is_visible().unwrap() {
@@ -162,6 +242,10 @@ async fn main() {
                     window.set_focus().unwrap();
                 }
             }
+            SystemTrayEvent::RightClick { .. } => {
+                // the entry can change outside the app, refresh before the menu shows
+                auto_start::sync_menu_item(&app.tray_handle().get_item(MENU_ITEM_AUTO_START));
+            }
             SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
                 MENUITEM_SHOW => {
                     let handle = app.app_handle();
@@ -171,6 +255,7 @@ async fn main() {
                 }
                 MENU_ITEM_QUIT => {
                     println!("system tray received quit");
+                    telemetry::shutdown();
                     std::process::exit(0);
                 }
                 MENU_ITEM_ABOUT => {
@@ -199,60 +284,7 @@ async fn main() {
                     }
                 }
                 MENU_ITEM_AUTO_START => {
-                    match auto_start::AUTO_LAUNCH.as_ref() {
-                        Some(v) => {
-                            let enabled = v.is_enabled();
-                            if enabled.is_err() {
-                                log::warn!(
-                                    "failed to get auto start menu item enabled state: {}",
-                                    enabled.as_ref().err().unwrap()
-                                );
-                                if let Err(e) = app
-                                    .tray_handle()
-                                    .get_item(MENU_ITEM_AUTO_START)
-                                    .set_enabled(false)
-                                {
-                                    log::warn!(
-                                        "failed to set auto start menu item to disabled: {}",
-                                        e
-                                    )
-                                }
-                            }
-
-                            if enabled.is_ok() && enabled.unwrap() {
-                                if let Err(e) = v.disable() {
-                                    log::warn!("failed to disable auto start: {}", e);
-                                } else {
-                                    if let Err(e) = app
-                                        .tray_handle()
-                                        .get_item(MENU_ITEM_AUTO_START)
-                                        .set_selected(false)
-                                    {
-                                        log::warn!(
-                                            "failed to set auto start menu item to disabled: {}",
-                                            e
-                                        )
-                                    }
-                                }
-                            } else {
-                                if let Err(e) = v.enable() {
-                                    log::warn!("failed to enable auto start: {}", e);
-                                } else {
-                                    if let Err(e) = app
-                                        .tray_handle()
-                                        .get_item(MENU_ITEM_AUTO_START)
-                                        .set_selected(true)
-                                    {
-                                        log::warn!(
-                                            "failed to set auto start menu item to disabled: {}",
-                                            e
-                                        )
-                                    }
-                                }
-                            }
-                        }
-                        None => {}
-                    };
+                    auto_start::toggle(&app.tray_handle().get_item(MENU_ITEM_AUTO_START));
                 }
                 _ => {}
             },
@@ -276,6 +308,11 @@ async fn main() {
                     api.prevent_close();
                 }
             }
+            // trays without click events (libappindicator) never re-sync the menu
+            tauri::WindowEvent::Focused(true) if event.window().label() == "main" => {
+                let tray = event.window().app_handle().tray_handle();
+                auto_start::sync_menu_item(&tray.get_item(MENU_ITEM_AUTO_START));
+            }
             _ => {}
         })
         .build(tauri::generate_context!())
@@ -299,7 +336,9 @@ async fn main() {
         }
     }
 
-    _ = config::init();
+    if let Err(e) = config::init() {
+        log::error!("failed to init config: {:#}", e);
+    }
 
     #[allow(unused)]
     app.run(|app, event| {});
@@ -0,0 +1,282 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, service::job};
+
+const METRICS_SETTINGS_KEY: &str = "metrics_settings";
+/// a client counts as active for this long after its last request
+const ACTIVE_CLIENT_WINDOW: Duration = Duration::from_secs(300);
+/// upper bounds of the proving duration buckets in seconds, proofs take
+/// anywhere from a second to several minutes
+const DURATION_BUCKETS: [f64; 12] = [
+    0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+];
+
+lazy_static! {
+    static ref SETTINGS: RwLock<MetricsSettings> = RwLock::new(MetricsSettings::default());
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// serve `/metrics` on the rpc server, to loopback clients only
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DecryptFailure {
+    NoPublicKey,
+    Ecdh,
+    Aes,
+}
+
+impl DecryptFailure {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DecryptFailure::NoPublicKey => "no_public_key",
+            DecryptFailure::Ecdh => "ecdh",
+            DecryptFailure::Aes => "aes",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// cumulative count per entry of `DURATION_BUCKETS`
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// by method and outcome
+    requests: BTreeMap<(String, &'static str), u64>,
+    durations: BTreeMap<String, Histogram>,
+    decrypt_failures: BTreeMap<DecryptFailure, u64>,
+    clients: HashMap<String, Instant>,
+}
+
+/// Counts a finished rpc call.
+pub fn record_request(method: &str, success: bool) {
+    let outcome = match success {
+        true => "success",
+        false => "error",
+    };
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics
+        .requests
+        .entry((method.to_string(), outcome))
+        .or_default() += 1;
+}
+
+/// Adds the time a backend call took to the method's histogram.
+pub fn record_duration(method: &str, duration: Duration) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics
+        .durations
+        .entry(method.to_string())
+        .or_default()
+        .observe(duration.as_secs_f64());
+}
+
+pub fn record_decrypt_failure(failure: DecryptFailure) {
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics.decrypt_failures.entry(failure).or_default() += 1;
+}
+
+/// Marks a client, by public key fingerprint, as active.
+pub fn record_client(fingerprint: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    let now = Instant::now();
+    metrics.clients.insert(fingerprint.to_string(), now);
+    metrics
+        .clients
+        .retain(|_, seen| now.duration_since(*seen) < ACTIVE_CLIENT_WINDOW);
+}
+
+pub fn enabled() -> bool {
+    SETTINGS.read().unwrap().enabled
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// All metrics in the prometheus text format.
+pub fn render() -> String {
+    let mut out = String::new();
+    let mut metrics = METRICS.lock().unwrap();
+
+    header(
+        &mut out,
+        "aleo_acc_requests_total",
+        "counter",
+        "Rpc calls by method and outcome.",
+    );
+    for ((method, outcome), count) in &metrics.requests {
+        let _ = writeln!(
+            out,
+            "aleo_acc_requests_total{{method=\"{}\",outcome=\"{}\"}} {}",
+            escape_label(method),
+            outcome,
+            count
+        );
+    }
+
+    header(
+        &mut out,
+        "aleo_acc_proving_duration_seconds",
+        "histogram",
+        "Time spent in the proving backend by method.",
+    );
+    for (method, histogram) in &metrics.durations {
+        let method = escape_label(method);
+        for (count, bound) in histogram.buckets.iter().zip(DURATION_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "aleo_acc_proving_duration_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                method, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "aleo_acc_proving_duration_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}",
+            method, histogram.count
+        );
+        let _ = writeln!(
+            out,
+            "aleo_acc_proving_duration_seconds_sum{{method=\"{}\"}} {}",
+            method, histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "aleo_acc_proving_duration_seconds_count{{method=\"{}\"}} {}",
+            method, histogram.count
+        );
+    }
+
+    header(
+        &mut out,
+        "aleo_acc_queue_depth",
+        "gauge",
+        "Backend calls currently waiting or running.",
+    );
+    let _ = writeln!(out, "aleo_acc_queue_depth {}", job::running_jobs());
+
+    header(
+        &mut out,
+        "aleo_acc_decrypt_failures_total",
+        "counter",
+        "Requests rejected before decryption finished, by reason.",
+    );
+    for failure in [
+        DecryptFailure::NoPublicKey,
+        DecryptFailure::Ecdh,
+        DecryptFailure::Aes,
+    ] {
+        let count = metrics.decrypt_failures.get(&failure).copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "aleo_acc_decrypt_failures_total{{reason=\"{}\"}} {}",
+            failure.as_str(),
+            count
+        );
+    }
+
+    let now = Instant::now();
+    metrics
+        .clients
+        .retain(|_, seen| now.duration_since(*seen) < ACTIVE_CLIENT_WINDOW);
+    header(
+        &mut out,
+        "aleo_acc_active_clients",
+        "gauge",
+        "Distinct client keys seen in the last 5 minutes.",
+    );
+    let _ = writeln!(out, "aleo_acc_active_clients {}", metrics.clients.len());
+
+    if let Some(usage) = memory_stats::memory_stats() {
+        header(
+            &mut out,
+            "process_resident_memory_bytes",
+            "gauge",
+            "Resident memory size in bytes.",
+        );
+        let _ = writeln!(out, "process_resident_memory_bytes {}", usage.physical_mem);
+        header(
+            &mut out,
+            "process_virtual_memory_bytes",
+            "gauge",
+            "Virtual memory size in bytes.",
+        );
+        let _ = writeln!(out, "process_virtual_memory_bytes {}", usage.virtual_mem);
+    }
+
+    out
+}
+
+impl Config {
+    pub fn get_metrics_settings(&self) -> Result<MetricsSettings> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(METRICS_SETTINGS_KEY)? {
+            Some(v) => serde_json::from_slice(&v).context("cant parse metrics settings"),
+            None => Ok(MetricsSettings::default()),
+        }
+    }
+
+    pub fn set_metrics_settings(&self, settings: &MetricsSettings) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(METRICS_SETTINGS_KEY, &serde_json::to_vec(settings)?)
+    }
+}
+
+pub fn init() {
+    match Config::get_config().get_metrics_settings() {
+        Ok(settings) => *SETTINGS.write().unwrap() = settings,
+        Err(e) => log::warn!("failed to load metrics settings: {:#}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_metrics_settings() -> MetricsSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn set_metrics_settings(settings: MetricsSettings) -> Result<(), String> {
+    Config::get_config()
+        .set_metrics_settings(&settings)
+        .map_err(|e| e.to_string())?;
+    *SETTINGS.write().unwrap() = settings;
+    Ok(())
+}
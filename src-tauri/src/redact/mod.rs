@@ -0,0 +1,318 @@
+use std::{borrow::Cow, sync::RwLock};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex, Replacer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+
+const MASK: &str = "***";
+const RECORD_MASK: &str = "{record redacted}";
+/// entry every record plaintext has, at its top level
+const RECORD_NONCE: &str = "_nonce";
+const REDACTION_SETTINGS_KEY: &str = "redaction_settings";
+
+lazy_static! {
+    /// private, view and compute keys, the prefix is kept so logs stay readable
+    static ref SECRET_KEY: Regex =
+        Regex::new(r"(APrivateKey1|AViewKey1|AComputeKey1)[1-9A-HJ-NP-Za-km-z]+").unwrap();
+    static ref REDACTOR: RwLock<Redactor> = RwLock::new(Redactor::new(&RedactionSettings::default()));
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionSettings {
+    /// names of fields whose values are always masked
+    pub fields: Vec<String>,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        let fields = [
+            "private_key",
+            "view_key",
+            "password",
+            "record",
+            "fee_record",
+            "input_record",
+            "first_record",
+            "second_record",
+        ];
+        Self {
+            fields: fields.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+struct Redactor {
+    settings: RedactionSettings,
+    /// `field: value`, `field=value` and `"field": "value"` in free text
+    field_pattern: Option<Regex>,
+}
+
+impl Redactor {
+    fn new(settings: &RedactionSettings) -> Self {
+        let fields: Vec<String> = settings
+            .fields
+            .iter()
+            .filter(|v| !v.is_empty())
+            .map(|v| regex::escape(v))
+            .collect();
+        let field_pattern = match fields.is_empty() {
+            true => None,
+            false => Regex::new(&format!(
+                r#"(?i)("?\b(?:{})"?\s*[:=]\s*)({}|"(?:[^"\\]|\\.)*"|[^\s,}}\]]+)"#,
+                fields.join("|"),
+                regex::escape(RECORD_MASK)
+            ))
+            .ok(),
+        };
+        Self {
+            settings: settings.clone(),
+            field_pattern,
+        }
+    }
+
+    fn redact_str<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = replace_all(Cow::Borrowed(text), &SECRET_KEY, |caps: &Captures| {
+            format!("{}{}", &caps[1], MASK)
+        });
+        let mut text = mask_records(text);
+        if let Some(pattern) = &self.field_pattern {
+            // a masked record stays as it is, its closing brace is part of the mask
+            text = replace_all(text, pattern, |caps: &Captures| match &caps[2] {
+                RECORD_MASK => caps[0].to_string(),
+                _ => format!("{}\"{}\"", &caps[1], MASK),
+            });
+        }
+        text
+    }
+
+    fn is_sensitive_field(&self, name: &str) -> bool {
+        self.settings
+            .fields
+            .iter()
+            .any(|v| v.eq_ignore_ascii_case(name))
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(v) => {
+                let redacted = match self.redact_str(v) {
+                    Cow::Owned(redacted) => Some(redacted),
+                    Cow::Borrowed(_) => None,
+                };
+                if let Some(redacted) = redacted {
+                    *v = redacted;
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_value(v)),
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if self.is_sensitive_field(key) && !v.is_null() {
+                        *v = Value::String(MASK.to_string());
+                    } else {
+                        self.redact_value(v);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn replace_all<'a, R: Replacer>(text: Cow<'a, str>, pattern: &Regex, rep: R) -> Cow<'a, str> {
+    if !pattern.is_match(&text) {
+        return text;
+    }
+    Cow::Owned(pattern.replace_all(&text, rep).into_owned())
+}
+
+/// Masks the record plaintexts in `text`, the innermost `{...}` around each
+/// `_nonce` entry. Members can be nested to any depth.
+fn mask_records(text: Cow<'_, str>) -> Cow<'_, str> {
+    if !text.contains(RECORD_NONCE) {
+        return text;
+    }
+    let mut masked = String::with_capacity(text.len());
+    let mut rest: &str = &text;
+    while let Some(pos) = rest.find(RECORD_NONCE) {
+        match record_bounds(rest, pos) {
+            Some((start, end)) => {
+                masked.push_str(&rest[..start]);
+                masked.push_str(RECORD_MASK);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                let end = pos + RECORD_NONCE.len();
+                masked.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+        }
+    }
+    masked.push_str(rest);
+    Cow::Owned(masked)
+}
+
+/// Byte offsets of the braces enclosing `pos`, none when they are unbalanced.
+fn record_bounds(text: &str, pos: usize) -> Option<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let start = (0..pos).rev().find(|&i| match bytes[i] {
+        b'}' => {
+            depth += 1;
+            false
+        }
+        b'{' if depth == 0 => true,
+        b'{' => {
+            depth -= 1;
+            false
+        }
+        _ => false,
+    })?;
+    let mut depth = 0;
+    let end = (pos..bytes.len()).find(|&i| match bytes[i] {
+        b'{' => {
+            depth += 1;
+            false
+        }
+        b'}' if depth == 0 => true,
+        b'}' => {
+            depth -= 1;
+            false
+        }
+        _ => false,
+    })?;
+    Some((start, end))
+}
+
+/// Masks Aleo secrets, record plaintexts and configured fields in `text`.
+pub fn redact_str(text: &str) -> Cow<'_, str> {
+    REDACTOR.read().unwrap().redact_str(text)
+}
+
+/// Same as `redact_str` for every string in `value`, values of configured
+/// fields are masked as a whole.
+pub fn redact_value(value: &mut Value) {
+    REDACTOR.read().unwrap().redact_value(value)
+}
+
+pub fn redact_jsonrpc_error(error: &mut jsonrpc_core::Error) {
+    error.message = redact_str(&error.message).into_owned();
+    if let Some(data) = error.data.as_mut() {
+        redact_value(data);
+    }
+}
+
+/// Redacts the errors in a response before it leaves the server.
+pub fn redact_jsonrpc_response(response: &mut jsonrpc_core::Response) {
+    let outputs = match response {
+        jsonrpc_core::Response::Single(v) => std::slice::from_mut(v),
+        jsonrpc_core::Response::Batch(v) => v.as_mut_slice(),
+    };
+    for output in outputs {
+        if let jsonrpc_core::Output::Failure(failure) = output {
+            redact_jsonrpc_error(&mut failure.error);
+        }
+    }
+}
+
+fn apply_settings(settings: &RedactionSettings) {
+    *REDACTOR.write().unwrap() = Redactor::new(settings);
+}
+
+impl Config {
+    pub fn get_redaction_settings(&self) -> Result<RedactionSettings> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(REDACTION_SETTINGS_KEY)? {
+            Some(v) => serde_json::from_slice(&v).context("cant parse redaction settings"),
+            None => Ok(RedactionSettings::default()),
+        }
+    }
+
+    pub fn set_redaction_settings(&self, settings: &RedactionSettings) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(REDACTION_SETTINGS_KEY, &serde_json::to_vec(settings)?)
+    }
+}
+
+pub fn init() {
+    match Config::get_config().get_redaction_settings() {
+        Ok(settings) => apply_settings(&settings),
+        Err(e) => log::warn!("failed to load redaction settings: {:#}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_redaction_settings() -> RedactionSettings {
+    REDACTOR.read().unwrap().settings.clone()
+}
+
+#[tauri::command]
+pub fn set_redaction_settings(settings: RedactionSettings) -> Result<(), String> {
+    Config::get_config()
+        .set_redaction_settings(&settings)
+        .map_err(|e| e.to_string())?;
+    apply_settings(&settings);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor::new(&RedactionSettings::default())
+    }
+
+    #[test]
+    fn nested_records_are_masked_whole() {
+        let record = "{ owner: aleo1qqq.private, data: { inner: { amount: 5u64.private }, \
+                      flag: true.private }, _nonce: 1234group.public }";
+        let text = format!("cant spend {} in the fee", record);
+        assert_eq!(
+            redactor().redact_str(&text),
+            "cant spend {record redacted} in the fee"
+        );
+
+        let text = format!(
+            "{{ \"error\": {}, \"other\": {}, \"code\": 1 }}",
+            record, record
+        );
+        assert_eq!(
+            redactor().redact_str(&text),
+            "{ \"error\": {record redacted}, \"other\": {record redacted}, \"code\": 1 }"
+        );
+    }
+
+    #[test]
+    fn secret_keys_keep_only_their_prefix() {
+        for prefix in ["APrivateKey1", "AViewKey1", "AComputeKey1"] {
+            let text = format!("bad key {}zkp8CZNn3yeCseEtxuVPbDCwSyhGW6yZKUYKfgXm", prefix);
+            assert_eq!(
+                redactor().redact_str(&text),
+                format!("bad key {}***", prefix)
+            );
+        }
+    }
+
+    #[test]
+    fn field_values_are_masked() {
+        let redactor = redactor();
+        assert_eq!(
+            redactor.redact_str("password=hunter2, port=8080"),
+            "password=\"***\", port=8080"
+        );
+        assert_eq!(
+            redactor.redact_str(r#"{"view_key": "a \"quoted\" key", "port": 8080}"#),
+            r#"{"view_key": "***", "port": 8080}"#
+        );
+        assert_eq!(
+            redactor.redact_str("record: { owner: aleo1qqq.private, _nonce: 1group.public }"),
+            "record: {record redacted}"
+        );
+        assert_eq!(redactor.redact_str("nothing to hide"), "nothing to hide");
+    }
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    config::Locked,
+    redact,
+    worker::{BackendError, BackendPanic, JobCancelled, WorkerCrashed},
+};
+
+/// Every error the rpc server returns besides the standard json-rpc ones.
+/// Codes never change meaning, `data.kind` holds the same variant as a string.
+/// 1247 and 1248 are reserved, they were never returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    AesDecrypt,
+    NoPublicKey,
+    Ecdh,
+    /// the proving backend panicked, the request failed but the server keeps running
+    BackendPanic,
+    /// the worker process died without answering
+    WorkerCrashed,
+    JobCancelled,
+    InvalidPrivateKey,
+    ProgramNotFound,
+    InsufficientBalance,
+    RecordAlreadySpent,
+    QueryUnreachable,
+    ProvingFailed,
+    /// the config has not been unlocked with the password yet
+    Locked,
+    /// any other backend failure
+    Internal,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 14] = [
+        ErrorKind::AesDecrypt,
+        ErrorKind::NoPublicKey,
+        ErrorKind::Ecdh,
+        ErrorKind::BackendPanic,
+        ErrorKind::WorkerCrashed,
+        ErrorKind::JobCancelled,
+        ErrorKind::InvalidPrivateKey,
+        ErrorKind::ProgramNotFound,
+        ErrorKind::InsufficientBalance,
+        ErrorKind::RecordAlreadySpent,
+        ErrorKind::QueryUnreachable,
+        ErrorKind::ProvingFailed,
+        ErrorKind::Locked,
+        ErrorKind::Internal,
+    ];
+
+    pub fn code(&self) -> i64 {
+        match self {
+            ErrorKind::AesDecrypt => 1234,
+            ErrorKind::NoPublicKey => 1235,
+            ErrorKind::Ecdh => 1236,
+            ErrorKind::BackendPanic => 1237,
+            ErrorKind::WorkerCrashed => 1238,
+            ErrorKind::JobCancelled => 1239,
+            ErrorKind::InvalidPrivateKey => 1240,
+            ErrorKind::ProgramNotFound => 1241,
+            ErrorKind::InsufficientBalance => 1242,
+            ErrorKind::RecordAlreadySpent => 1243,
+            ErrorKind::QueryUnreachable => 1244,
+            ErrorKind::ProvingFailed => 1245,
+            ErrorKind::Locked => 1246,
+            ErrorKind::Internal => 500,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ErrorKind::AesDecrypt => "cant decrypt request",
+            ErrorKind::NoPublicKey => "no public key found",
+            ErrorKind::Ecdh => "failed to generate shared secret",
+            ErrorKind::BackendPanic => "backend panicked",
+            ErrorKind::WorkerCrashed => "worker crashed",
+            ErrorKind::JobCancelled => "job cancelled",
+            ErrorKind::InvalidPrivateKey => "invalid private key",
+            ErrorKind::ProgramNotFound => "program not found",
+            ErrorKind::InsufficientBalance => "insufficient balance",
+            ErrorKind::RecordAlreadySpent => "record already spent",
+            ErrorKind::QueryUnreachable => "query endpoint unreachable",
+            ErrorKind::ProvingFailed => "proving failed",
+            ErrorKind::Locked => "service is locked",
+            ErrorKind::Internal => "internal error",
+        }
+    }
+
+    /// Kind of an error returned by a rpc method. Backend errors carry the
+    /// kind they were classified with where they happened.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if err.is::<BackendPanic>() {
+            return ErrorKind::BackendPanic;
+        }
+        if err.is::<WorkerCrashed>() {
+            return ErrorKind::WorkerCrashed;
+        }
+        if err.is::<JobCancelled>() {
+            return ErrorKind::JobCancelled;
+        }
+        if err.is::<Locked>() {
+            return ErrorKind::Locked;
+        }
+        if let Some(err) = err.downcast_ref::<BackendError>() {
+            return err.kind;
+        }
+        Self::classify(err)
+    }
+
+    /// Classifies an error of the backend. The io errors in its chain are
+    /// checked first, aleowrap has no error types of its own so anything else
+    /// is matched on the message.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        let unreachable = err.chain().any(|cause| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|v| is_unreachable(v.kind()))
+        });
+        if unreachable {
+            return ErrorKind::QueryUnreachable;
+        }
+        let text = format!("{:#}", err).to_lowercase();
+        MESSAGE_PATTERNS
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|v| text.contains(v)))
+            .map(|(kind, _)| *kind)
+            .unwrap_or(ErrorKind::Internal)
+    }
+}
+
+fn is_unreachable(kind: std::io::ErrorKind) -> bool {
+    use std::io::ErrorKind as Io;
+    matches!(
+        kind,
+        Io::ConnectionRefused
+            | Io::ConnectionReset
+            | Io::ConnectionAborted
+            | Io::NotConnected
+            | Io::TimedOut
+    )
+}
+
+/// checked in order, network errors first since they often name the program
+/// or record that was being fetched
+const MESSAGE_PATTERNS: [(ErrorKind, &[&str]); 6] = [
+    (
+        ErrorKind::QueryUnreachable,
+        &[
+            "connection refused",
+            "connection failed",
+            "connection reset",
+            "dns failed",
+            "timed out",
+            "error sending request",
+            "network is unreachable",
+        ],
+    ),
+    (
+        ErrorKind::RecordAlreadySpent,
+        &["already spent", "serial number already exists"],
+    ),
+    (
+        ErrorKind::InsufficientBalance,
+        &["insufficient", "not enough balance", "not enough credits"],
+    ),
+    (ErrorKind::InvalidPrivateKey, &["private key", "privatekey"]),
+    (
+        ErrorKind::ProgramNotFound,
+        &[
+            "program not found",
+            "failed to fetch program",
+            "missing program",
+        ],
+    ),
+    (
+        ErrorKind::ProvingFailed,
+        &["prove", "proving", "synthesize", "constraint"],
+    ),
+];
+
+/// Error of `kind` with `{ "kind": .., "detail": .. }` as data, redacted.
+pub fn rpc_error(kind: ErrorKind, detail: Option<String>) -> jsonrpc_core::Error {
+    let mut error = jsonrpc_core::Error::new(jsonrpc_core::ErrorCode::ServerError(kind.code()));
+    error.message = kind.message().to_string();
+    error.data = Some(json!({
+        "kind": kind,
+        "detail": detail,
+    }));
+    redact::redact_jsonrpc_error(&mut error);
+    error
+}
+
+pub fn from_anyhow(err: &anyhow::Error) -> jsonrpc_core::Error {
+    let detail = match err.downcast_ref::<BackendPanic>() {
+        Some(panic) => panic.0.clone(),
+        None => format!("{:#}", err),
+    };
+    rpc_error(ErrorKind::of(err), Some(detail))
+}
+
+/// The catalogue as OpenRPC error objects, keyed by kind.
+pub fn catalogue() -> Value {
+    let mut errors = serde_json::Map::new();
+    for kind in ErrorKind::ALL {
+        let name = serde_json::to_value(kind).unwrap_or_default();
+        errors.insert(
+            name.as_str().unwrap_or_default().to_string(),
+            json!({
+                "code": kind.code(),
+                "message": kind.message(),
+                "data": { "kind": kind },
+            }),
+        );
+    }
+    Value::Object(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io};
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn typed_errors_keep_their_kind() {
+        assert_eq!(
+            ErrorKind::of(&anyhow::Error::new(JobCancelled)),
+            ErrorKind::JobCancelled
+        );
+        assert_eq!(
+            ErrorKind::of(&anyhow::Error::new(Locked)),
+            ErrorKind::Locked
+        );
+        let backend = BackendError {
+            kind: ErrorKind::ProgramNotFound,
+            message: "connection refused".to_string(),
+        };
+        assert_eq!(
+            ErrorKind::of(&anyhow::Error::new(backend)),
+            ErrorKind::ProgramNotFound
+        );
+    }
+
+    #[test]
+    fn io_errors_in_the_chain_are_unreachable() {
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::ConnectionRefused))
+            .context("cant fetch program hello.aleo");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::QueryUnreachable);
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn messages_are_the_fallback() {
+        let kind = |message: &str| ErrorKind::of(&anyhow!("{}", message));
+        assert_eq!(
+            kind("Serial number already exists"),
+            ErrorKind::RecordAlreadySpent
+        );
+        assert_eq!(
+            kind("failed to fetch program: timed out"),
+            ErrorKind::QueryUnreachable
+        );
+        assert_eq!(
+            kind("Failed to fetch program hello.aleo"),
+            ErrorKind::ProgramNotFound
+        );
+        assert_eq!(kind("something else"), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let codes: HashSet<i64> = ErrorKind::ALL.iter().map(ErrorKind::code).collect();
+        assert_eq!(codes.len(), ErrorKind::ALL.len());
+    }
+}
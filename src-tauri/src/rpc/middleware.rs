@@ -1,43 +1,69 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use hyper::header::{HeaderValue, CONTENT_TYPE};
+use anyhow::{Context, Result};
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, StatusCode};
+use jsonrpc_core::futures::future;
 use jsonrpc_core::types::request::Request as JsonRpcRequest;
-use jsonrpc_core::{Id, MethodCall, Params};
-use serde_json::{from_slice, Value};
-use tokio::sync::oneshot::Sender;
+use jsonrpc_core::{Call, Id, MethodCall, Output, Params};
+use serde_json::from_slice;
+use tokio::{sync::oneshot::Sender, task::JoinHandle};
+use tracing::Instrument;
 
-use crate::tls;
-
-const CODE_AES_DECRYPT_ERROR: i64 = 1234;
-const CODE_NO_PUBKEY_FOUND: i64 = 1235;
-const CODE_ECDH_ERROR: i64 = 1236;
+use super::errors::{rpc_error, ErrorKind};
+use crate::{
+    audit::{self, AuditRecord},
+    config::Locked,
+    metrics::{self, DecryptFailure},
+    redact, tls,
+};
 
 const HEADER_PUBLIC_KEY: &str = "Public-Key";
+/// `legacy` returns transactions and costs as plain strings
+const HEADER_RESULT_FORMAT: &str = "Result-Format";
+/// 1: P-256 ECDH with the `Public-Key` header, HKDF-SHA256 and AES-GCM over the body
+pub const ENVELOPE_VERSIONS: [u32; 1] = [1];
+pub const TRANSPORTS: [&str; 1] = ["http"];
+
+/// Response for a request that fails before it reaches a method.
+type ErrorResponse = Box<Response<Body>>;
 
-async fn handle_request(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+/// Fingerprint of the client public key, attached to decrypted requests.
+#[derive(Clone)]
+struct ClientFingerprint(String);
+
+async fn handle_request(
+    req: Request<Body>,
+    remote: SocketAddr,
+) -> Result<Response<Body>, hyper::Error> {
     match (req.method(), req.uri().path()) {
         (&hyper::Method::OPTIONS, _) => {
             let resp = Response::builder()
                 .header("Access-Control-Allow-Origin", "*")
                 .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
-                .header("Access-Control-Allow-Headers", "Content-Type, Public-Key")
+                .header(
+                    "Access-Control-Allow-Headers",
+                    "Content-Type, Public-Key, Result-Format",
+                )
                 .body(Body::empty())
                 .unwrap();
             Ok(resp)
         }
         (&hyper::Method::GET, "/discovery") => {
             let res = super::rpc::RPC_HANDER
-                .handle_rpc_request(JsonRpcRequest::Single(jsonrpc_core::Call::MethodCall(
-                    MethodCall {
+                .handle_rpc_request(
+                    JsonRpcRequest::Single(jsonrpc_core::Call::MethodCall(MethodCall {
                         jsonrpc: Some(jsonrpc_core::Version::V2),
                         method: "discovery".to_string(),
-                        params: Params::None,
+                        params: discovery_params(req.uri().query()),
                         id: Id::Num(1),
-                    },
-                )))
+                    })),
+                    (),
+                )
                 .await;
 
             if let Some(response) = res {
@@ -47,7 +73,10 @@ async fn handle_request(req: Request<Body>) -> Result<Response<Body>, hyper::Err
                     .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
                     .header("Access-Control-Allow-Origin", "*")
                     .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
-                    .header("Access-Control-Allow-Headers", "Content-Type, Public-Key")
+                    .header(
+                        "Access-Control-Allow-Headers",
+                        "Content-Type, Public-Key, Result-Format",
+                    )
                     .body(Body::from(response_body))
                     .unwrap();
                 return Ok(body);
@@ -62,8 +91,44 @@ async fn handle_request(req: Request<Body>) -> Result<Response<Body>, hyper::Err
 
             Ok(response)
         }
+        (&hyper::Method::GET, "/openrpc.json") => {
+            let response = Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
+                .header(
+                    "Access-Control-Allow-Headers",
+                    "Content-Type, Public-Key, Result-Format",
+                )
+                .body(Body::from(
+                    serde_json::to_string(super::openrpc::document()).unwrap_or_default(),
+                ))
+                .unwrap();
+            Ok(response)
+        }
+        (&hyper::Method::GET, "/metrics") if metrics::enabled() => {
+            // metrics reveal usage patterns, keep them on this machine
+            // v4 clients of a dual stack listener show up as v4-mapped v6
+            if !remote.ip().to_canonical().is_loopback() {
+                return Ok(Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .body(Body::empty())
+                    .unwrap());
+            }
+            let response = Response::builder()
+                .status(StatusCode::OK)
+                .header(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("text/plain; version=0.0.4"),
+                )
+                .body(Body::from(metrics::render()))
+                .unwrap();
+            Ok(response)
+        }
         (&hyper::Method::POST, "/") => {
-            let mut response = aes_decode_middleware(req).await;
+            let span = tracing::info_span!(target: "rpc", "rpc_request", remote = %remote);
+            let mut response = aes_decode_middleware(req).instrument(span).await;
             let headers = response.headers_mut();
             headers.insert("Access-Control-Allow-Origin", "*".parse().unwrap());
             headers.insert(
@@ -72,7 +137,7 @@ async fn handle_request(req: Request<Body>) -> Result<Response<Body>, hyper::Err
             );
             headers.insert(
                 "Access-Control-Allow-Headers",
-                "Content-Type, Public-Key".parse().unwrap(),
+                "Content-Type, Public-Key, Result-Format".parse().unwrap(),
             );
 
             Ok(response)
@@ -88,49 +153,82 @@ async fn aes_decode_middleware(req: Request<Body>) -> Response<Body> {
     let (parts, body) = req.into_parts();
     let body_bytes = hyper::body::to_bytes(body).await.unwrap();
 
-    let pk = match read_header_hex_as_bytes(parts.headers.get(HEADER_PUBLIC_KEY)) {
+    let decrypted = tracing::info_span!(target: "rpc", "decrypt", bytes = body_bytes.len())
+        .in_scope(|| decrypt_request(&parts.headers, &body_bytes));
+    let (decoded_body, pk) = match decrypted {
+        Ok(v) => v,
+        Err(response) => return *response,
+    };
+
+    let new_body = Body::from(decoded_body);
+
+    let fingerprint = hex::encode(tls::pubkey_to_fingerprint(&pk));
+    metrics::record_client(&fingerprint);
+    let mut parts = parts;
+    parts.extensions.insert(ClientFingerprint(fingerprint));
+    let new_req = Request::from_parts(parts, new_body);
+
+    handle_rpc(new_req).await
+}
+
+/// Decrypts the body with the key agreed with the client's `Public-Key`.
+/// Returns the plaintext and the client public key, or the error response.
+fn decrypt_request(
+    headers: &HeaderMap,
+    body_bytes: &[u8],
+) -> std::result::Result<(Vec<u8>, Vec<u8>), ErrorResponse> {
+    let pk = match read_header_hex_as_bytes(headers.get(HEADER_PUBLIC_KEY)) {
         Ok(v) => v,
         Err(e) => {
-            let mut error = jsonrpc_core::error::Error::new(jsonrpc_core::ErrorCode::ServerError(
-                CODE_NO_PUBKEY_FOUND,
-            ));
-            error.data = Some(Value::String(format!("{:#?}", e)));
-            error.message = "no public key found".to_string();
-            return jsonrpc_error_to_response(error);
+            metrics::record_decrypt_failure(DecryptFailure::NoPublicKey);
+            return Err(Box::new(jsonrpc_error_to_response(rpc_error(
+                ErrorKind::NoPublicKey,
+                Some(format!("{:#}", e)),
+            ))));
         }
     };
 
     let shared = match tls::generate_p256_shared_secret(&pk) {
         Ok(v) => v,
+        Err(e) if e.is::<Locked>() => {
+            return Err(Box::new(jsonrpc_error_to_response(rpc_error(
+                ErrorKind::Locked,
+                None,
+            ))));
+        }
         Err(e) => {
-            let mut error = jsonrpc_core::error::Error::new(jsonrpc_core::ErrorCode::ServerError(
-                CODE_ECDH_ERROR,
-            ));
-            error.data = Some(Value::String(format!("{:#?}", e)));
-            error.message = "failed to generate shared secret".to_string();
-            return jsonrpc_error_to_response(error);
+            metrics::record_decrypt_failure(DecryptFailure::Ecdh);
+            return Err(Box::new(jsonrpc_error_to_response(rpc_error(
+                ErrorKind::Ecdh,
+                Some(format!("{:#}", e)),
+            ))));
         }
     };
 
     let aes_key = tls::shared_secret_to_symmetric_secret(&shared);
 
-    let decoded_body = match tls::aes::aes_decode(&aes_key, &body_bytes) {
-        Ok(v) => v,
+    match tls::aes::aes_decode(&aes_key, body_bytes) {
+        Ok(v) => Ok((v, pk)),
         Err(e) => {
-            let mut error = jsonrpc_core::error::Error::new(jsonrpc_core::ErrorCode::ServerError(
-                CODE_AES_DECRYPT_ERROR,
-            ));
-            error.data = Some(serde_json::Value::String(format!("{:#?}", e)));
-            error.message = e.to_string();
-            return jsonrpc_error_to_response(error);
+            metrics::record_decrypt_failure(DecryptFailure::Aes);
+            Err(Box::new(jsonrpc_error_to_response(rpc_error(
+                ErrorKind::AesDecrypt,
+                Some(format!("{:#}", e)),
+            ))))
         }
-    };
-
-    let new_body = Body::from(decoded_body);
-
-    let new_req = Request::from_parts(parts, new_body);
+    }
+}
 
-    handle_rpc(new_req).await
+/// `?nonce=<hex>` of `GET /discovery` as params of the `discovery` method.
+fn discovery_params(query: Option<&str>) -> Params {
+    let nonce = query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|v| v.strip_prefix("nonce="));
+    match nonce {
+        Some(nonce) => Params::Array(vec![serde_json::Value::String(nonce.to_string())]),
+        None => Params::None,
+    }
 }
 
 fn read_header_hex_as_bytes(header: Option<&HeaderValue>) -> Result<Vec<u8>> {
@@ -147,7 +245,7 @@ fn read_header_hex_as_bytes(header: Option<&HeaderValue>) -> Result<Vec<u8>> {
 }
 
 async fn handle_rpc(req: Request<Body>) -> Response<Body> {
-    let (_, body) = req.into_parts();
+    let (parts, body) = req.into_parts();
     let body_bytes = hyper::body::to_bytes(body).await.unwrap();
 
     // Parse the request body as JSON-RPC request
@@ -161,12 +259,27 @@ async fn handle_rpc(req: Request<Body>) -> Response<Body> {
         }
     };
 
-    let res = super::rpc::RPC_HANDER
-        .handle_rpc_request(decoded_body)
+    let calls = method_calls(&decoded_body);
+    let methods: Vec<&str> = calls.iter().map(|v| v.method.as_str()).collect();
+    let legacy = parts
+        .headers
+        .get(HEADER_RESULT_FORMAT)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"legacy"));
+    let (res, durations) = super::rpc::LEGACY_RESULTS
+        .scope(legacy, dispatch(decoded_body))
+        .instrument(tracing::info_span!(target: "rpc", "dispatch", methods = ?methods))
         .await;
+    count_calls(&calls, res.as_ref());
+
+    if let Some(client) = parts.extensions.get::<ClientFingerprint>() {
+        audit_calls(&client.0, calls, res.as_ref(), &durations);
+    }
 
-    if let Some(response) = res {
+    if let Some(mut response) = res {
+        let _span = tracing::info_span!(target: "rpc", "response").entered();
+        redact::redact_jsonrpc_response(&mut response);
         let response_body = serde_json::to_string(&response).unwrap_or_default();
+        tracing::debug!(target: "rpc", "response of {} bytes", response_body.len());
         let body = Response::builder()
             .status(StatusCode::OK)
             .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
@@ -185,11 +298,118 @@ async fn handle_rpc(req: Request<Body>) -> Response<Body> {
     response
 }
 
-pub fn start_hyper(address: &SocketAddr) -> Sender<()> {
+/// Handles the calls of a batch concurrently like `handle_rpc_request`, but
+/// times each one. Durations are keyed by the id of the method call.
+async fn dispatch(
+    request: JsonRpcRequest,
+) -> (Option<jsonrpc_core::Response>, HashMap<Id, Duration>) {
+    let (calls, single) = match request {
+        JsonRpcRequest::Single(call) => (vec![call], true),
+        JsonRpcRequest::Batch(calls) => (calls, false),
+    };
+    let timed = future::join_all(calls.into_iter().map(|call| async move {
+        let id = match &call {
+            Call::MethodCall(v) => Some(v.id.clone()),
+            _ => None,
+        };
+        let start_time = Instant::now();
+        let output = super::rpc::RPC_HANDER.handle_call(call, ()).await;
+        (output, id.map(|id| (id, start_time.elapsed())))
+    }))
+    .await;
+
+    let mut outputs = Vec::new();
+    let mut durations = HashMap::new();
+    for (output, duration) in timed {
+        outputs.extend(output);
+        durations.extend(duration);
+    }
+    let response = if single {
+        outputs.pop().map(jsonrpc_core::Response::Single)
+    } else if outputs.is_empty() {
+        None
+    } else {
+        Some(jsonrpc_core::Response::Batch(outputs))
+    };
+    (response, durations)
+}
+
+fn method_calls(request: &JsonRpcRequest) -> Vec<MethodCall> {
+    let calls = match request {
+        JsonRpcRequest::Single(call) => std::slice::from_ref(call),
+        JsonRpcRequest::Batch(calls) => calls.as_slice(),
+    };
+    calls
+        .iter()
+        .filter_map(|call| match call {
+            Call::MethodCall(v) => Some(v.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Output of `call` in `response`, matched by id.
+fn find_output<'a>(outputs: &'a [Output], call: &MethodCall) -> Option<&'a Output> {
+    outputs.iter().find(|v| match v {
+        Output::Success(s) => s.id == call.id,
+        Output::Failure(f) => f.id == call.id,
+    })
+}
+
+fn response_outputs(response: Option<&jsonrpc_core::Response>) -> &[Output] {
+    match response {
+        Some(jsonrpc_core::Response::Single(v)) => std::slice::from_ref(v),
+        Some(jsonrpc_core::Response::Batch(v)) => v.as_slice(),
+        None => &[],
+    }
+}
+
+fn count_calls(calls: &[MethodCall], response: Option<&jsonrpc_core::Response>) {
+    let outputs = response_outputs(response);
+    for call in calls {
+        if let Some(output) = find_output(outputs, call) {
+            metrics::record_request(&call.method, matches!(output, Output::Success(_)));
+        }
+    }
+}
+
+/// Writes an audit entry for every method call, matched to its output by id.
+fn audit_calls(
+    client: &str,
+    calls: Vec<MethodCall>,
+    response: Option<&jsonrpc_core::Response>,
+    durations: &HashMap<Id, Duration>,
+) {
+    let outputs = response_outputs(response);
+    for call in calls {
+        let result = match find_output(outputs, &call) {
+            Some(Output::Success(s)) => Ok(&s.result),
+            Some(Output::Failure(f)) => Err(&f.error),
+            None => continue,
+        };
+        audit::record(AuditRecord {
+            client,
+            method: &call.method,
+            params: serde_json::to_value(&call.params).unwrap_or_default(),
+            result,
+            duration: durations.get(&call.id).copied().unwrap_or_default(),
+        });
+    }
+}
+
+/// Binds `address` and serves on a spawned task. Binding happens before
+/// returning, so a taken port is an error here. Also returns the bound address,
+/// which differs from `address` for port 0.
+pub fn start_hyper(address: &SocketAddr) -> Result<(Sender<()>, JoinHandle<()>, SocketAddr)> {
     // Create the Hyper server
-    let make_svc =
-        make_service_fn(|_conn| async { Ok::<_, hyper::Error>(service_fn(handle_request)) });
-    let server = hyper::Server::bind(address).serve(make_svc);
+    let make_svc = make_service_fn(|conn: &AddrStream| {
+        let remote = conn.remote_addr();
+        async move { Ok::<_, hyper::Error>(service_fn(move |req| handle_request(req, remote))) }
+    });
+    let server = hyper::Server::try_bind(address)
+        .with_context(|| format!("cant bind {}", address))?
+        .serve(make_svc);
+    let bound = server.local_addr();
 
     // Prepare some signal for when the server should start shutting down...
     let (tx, rx) = tokio::sync::oneshot::channel::<()>();
@@ -197,17 +417,18 @@ pub fn start_hyper(address: &SocketAddr) -> Sender<()> {
         rx.await.ok();
     });
 
-    tokio::spawn(async {
+    let task = tokio::spawn(async {
         // Start the server
         if let Err(err) = graceful.await {
-            eprintln!("Server error: {}", err);
+            log::error!("rpc server error: {}", err);
         }
     });
 
-    tx
+    Ok((tx, task, bound))
 }
 
-pub fn jsonrpc_error_to_response(err: jsonrpc_core::Error) -> hyper::Response<Body> {
+pub fn jsonrpc_error_to_response(mut err: jsonrpc_core::Error) -> hyper::Response<Body> {
+    redact::redact_jsonrpc_error(&mut err);
     let response_body = serde_json::to_string(&err).unwrap_or_default();
     Response::builder()
         .status(StatusCode::OK)
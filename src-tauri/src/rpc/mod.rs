@@ -1,18 +1,35 @@
+pub mod errors;
 pub mod middleware;
+pub mod openrpc;
+pub mod params;
 pub mod rpc;
+pub mod settings;
+pub mod types;
 
 use std::{
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    fs::{File, TryLockError},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
     sync::Mutex,
 };
 
+use anyhow::{Context, Result};
 use lazy_static::lazy_static;
-use tokio::sync::oneshot::Sender;
+use tokio::{sync::oneshot::Sender, task::JoinHandle};
 
-use crate::config::consts::RPC_PORT;
+use crate::{
+    config::{self, Config},
+    service::app,
+};
+
+/// Held by the running server, next to the address it publishes.
+const RPC_LOCK_FILE: &str = "rpc.lock";
 
 lazy_static! {
     static ref RPC_CLOSER: Mutex<Option<Sender<()>>> = Mutex::new(None);
+    static ref RPC_TASK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+    static ref RPC_ADDRESS: Mutex<Option<SocketAddr>> = Mutex::new(None);
+    static ref RPC_LOCK: Mutex<Option<File>> = Mutex::new(None);
 }
 
 #[tauri::command]
@@ -22,25 +39,119 @@ pub fn stop_rpc_server() {
         Some(v) => {
             let _ = v.send(());
             *rpc_closer = None;
+            *RPC_ADDRESS.lock().unwrap() = None;
+            if let Err(e) = Config::get_config().set_rpc_address(None) {
+                log::warn!("failed to remove rpc address: {:#}", e);
+            }
+            RPC_LOCK.lock().unwrap().take();
         }
         None => {}
     }
 }
 
-#[tauri::command]
-pub async fn run_rpc_server() {
-    if RPC_CLOSER.lock().unwrap().is_some() {
-        return;
+pub fn is_running() -> bool {
+    RPC_CLOSER.lock().unwrap().is_some()
+}
+
+/// Stops the server and waits for in-flight requests to finish.
+pub async fn shutdown_rpc_server() {
+    stop_rpc_server();
+    let task = RPC_TASK.lock().unwrap().take();
+    if let Some(task) = task {
+        let _ = task.await;
+        log::info!("rpc server stopped!");
     }
-    #[cfg(debug_assertions)]
-    let address: SocketAddr =
-        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), RPC_PORT));
+}
 
-    #[cfg(not(debug_assertions))]
-    let address = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), RPC_PORT));
+/// Address clients connect to, the bound one while running, then the one
+/// published by a server in another process, else the one in the settings.
+/// Unspecified addresses are reached over loopback.
+pub fn server_address() -> SocketAddr {
+    let running = *RPC_ADDRESS.lock().unwrap();
+    let mut address = running.or_else(published_address).unwrap_or_else(|| {
+        let settings = settings::settings();
+        SocketAddr::new(settings.bind_address, settings.port)
+    });
+    match address.ip() {
+        IpAddr::V4(v) if v.is_unspecified() => address.set_ip(Ipv4Addr::LOCALHOST.into()),
+        IpAddr::V6(v) if v.is_unspecified() => address.set_ip(Ipv6Addr::LOCALHOST.into()),
+        _ => {}
+    }
+    address
+}
+
+/// Address published by a server in another process. The server holds the
+/// lock file while it runs, an address nobody holds it for was left by a
+/// server that did not stop cleanly and is ignored.
+fn published_address() -> Option<SocketAddr> {
+    let address = Config::get_config().get_rpc_address().ok().flatten()?;
+    server_holds_lock().then_some(address)
+}
 
-    let close = middleware::start_hyper(&address);
+fn lock_path() -> Result<PathBuf> {
+    Ok(config::data_dir()?.join(RPC_LOCK_FILE))
+}
+
+/// Probes the lock with a shared lock, released again when the file closes.
+fn server_holds_lock() -> bool {
+    match lock_path().and_then(|path| Ok(File::open(path)?)) {
+        Ok(file) => matches!(file.try_lock_shared(), Err(TryLockError::WouldBlock)),
+        Err(_) => false,
+    }
+}
+
+/// Takes the lock for the server of this process, until the file is dropped.
+fn hold_lock() -> Result<File> {
+    let path = lock_path()?;
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("cant open {}", path.display()))?;
+    file.try_lock()
+        .with_context(|| format!("cant lock {}", path.display()))?;
+    Ok(file)
+}
+
+#[tauri::command]
+pub async fn run_rpc_server() -> Result<(), String> {
+    start_rpc_server().map_err(|e| {
+        let err_msg = format!("failed to start rpc server: {:#}", e);
+        log::error!("{}", err_msg);
+        app::error_dialog("rpc server error", err_msg.clone());
+        err_msg
+    })
+}
+
+/// Binds the address from the settings, or a free port if that fails and
+/// `port_fallback` is on. Needs a tokio runtime.
+pub fn start_rpc_server() -> Result<()> {
     let mut rpc_closer = RPC_CLOSER.lock().unwrap();
+    if rpc_closer.is_some() {
+        return Ok(());
+    }
+    let settings = settings::settings();
+    let address = SocketAddr::new(settings.bind_address, settings.port);
+
+    let (close, task, bound) = match middleware::start_hyper(&address) {
+        Ok(v) => v,
+        Err(e) if settings.port_fallback && settings.port != 0 => {
+            log::warn!("{:#}, falling back to a free port", e);
+            middleware::start_hyper(&SocketAddr::new(settings.bind_address, 0))?
+        }
+        Err(e) => return Err(e),
+    };
     *rpc_closer = Some(close);
-    log::info!("rpc server started!");
+    *RPC_TASK.lock().unwrap() = Some(task);
+    *RPC_ADDRESS.lock().unwrap() = Some(bound);
+    match hold_lock() {
+        Ok(file) => *RPC_LOCK.lock().unwrap() = Some(file),
+        Err(e) => log::warn!("{:#}, other processes wont find the rpc address", e),
+    }
+    if let Err(e) = Config::get_config().set_rpc_address(Some(bound)) {
+        log::warn!("failed to publish rpc address: {:#}", e);
+    }
+    log::info!("rpc server started on {}", bound);
+    Ok(())
 }
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::errors;
+use crate::config::consts::APP_NAME;
+
+/// methods and schemas generated from the `Rpc` trait by the build script
+const RPC_SCHEMA: &str = include_str!(concat!(env!("OUT_DIR"), "/rpc_schema.json"));
+
+pub const OPENRPC_VERSION: &str = "1.2.6";
+
+lazy_static! {
+    static ref DOCUMENT: Value = build_document();
+    static ref PARAMS: HashMap<String, Vec<ParamSpec>> = build_params();
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ParamSpec {
+    pub name: String,
+    pub required: bool,
+}
+
+#[derive(Deserialize)]
+struct MethodSpec {
+    name: String,
+    params: Vec<ParamSpec>,
+}
+
+fn build_document() -> Value {
+    let schema: Value = serde_json::from_str(RPC_SCHEMA).expect("invalid generated rpc schema");
+    json!({
+        "openrpc": OPENRPC_VERSION,
+        "info": {
+            "title": APP_NAME,
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "methods": schema["methods"],
+        "components": {
+            "schemas": schema["schemas"],
+            "errors": errors::catalogue(),
+        },
+    })
+}
+
+fn build_params() -> HashMap<String, Vec<ParamSpec>> {
+    let methods: Vec<MethodSpec> =
+        serde_json::from_value(DOCUMENT["methods"].clone()).expect("invalid generated rpc schema");
+    methods.into_iter().map(|v| (v.name, v.params)).collect()
+}
+
+/// Params of `method` in positional order, `None` for methods not in the trait.
+pub fn method_params(method: &str) -> Option<&'static [ParamSpec]> {
+    PARAMS.get(method).map(Vec::as_slice)
+}
+
+/// The OpenRPC document of the rpc server, served by `rpc.discover` and
+/// `/openrpc.json`.
+pub fn document() -> &'static Value {
+    &DOCUMENT
+}
@@ -0,0 +1,124 @@
+use jsonrpc_core::{
+    futures::future::{self, Either},
+    middleware::{Middleware, NoopFuture},
+    Call, Error, Output, Params,
+};
+use serde_json::{json, Value};
+
+use super::openrpc;
+
+/// Accepts named params for every method of the `Rpc` trait by turning them
+/// into positional ones before dispatch.
+#[derive(Default)]
+pub struct NamedParams;
+
+impl Middleware<()> for NamedParams {
+    type Future = NoopFuture;
+    type CallFuture = future::Ready<Option<Output>>;
+
+    fn on_call<F, X>(&self, call: Call, meta: (), next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, ()) -> X + Send + Sync,
+        X: std::future::Future<Output = Option<Output>> + Send + 'static,
+    {
+        let Call::MethodCall(mut method_call) = call else {
+            return Either::Right(next(call, meta));
+        };
+        let params = std::mem::replace(&mut method_call.params, Params::None);
+        match by_position(&method_call.method, params) {
+            Ok(params) => {
+                method_call.params = params;
+                Either::Right(next(Call::MethodCall(method_call), meta))
+            }
+            Err(error) => Either::Left(future::ready(Some(Output::from(
+                Err(error),
+                method_call.id,
+                method_call.jsonrpc,
+            )))),
+        }
+    }
+}
+
+/// Orders named params like the trait method. Positional params and methods
+/// outside the trait are left alone.
+pub fn by_position(method: &str, params: Params) -> Result<Params, Error> {
+    let (mut map, specs) = match (params, openrpc::method_params(method)) {
+        (Params::Map(map), Some(specs)) => (map, specs),
+        (params, _) => return Ok(params),
+    };
+
+    let mut values = Vec::with_capacity(specs.len());
+    let mut missing = Vec::new();
+    for spec in specs {
+        let value = map.remove(&spec.name).unwrap_or(Value::Null);
+        if spec.required && value.is_null() {
+            missing.push(spec.name.as_str());
+        }
+        values.push(value);
+    }
+    let unknown: Vec<&str> = map.keys().map(String::as_str).collect();
+
+    if missing.is_empty() && unknown.is_empty() {
+        return Ok(Params::Array(values));
+    }
+    let mut problems = Vec::new();
+    if !missing.is_empty() {
+        problems.push(format!("missing required params: {}", missing.join(", ")));
+    }
+    if !unknown.is_empty() {
+        problems.push(format!("unknown params: {}", unknown.join(", ")));
+    }
+    let mut error = Error::invalid_params(problems.join("; "));
+    error.data = Some(json!({
+        "missing": missing,
+        "unknown": unknown,
+        "expected": specs.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(),
+    }));
+    Err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::ErrorCode;
+
+    use super::*;
+
+    fn named(value: Value) -> Params {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn named_params_follow_the_trait_order() {
+        let params = named(json!({ "amount": 5, "record": "r", "private_key": "k" }));
+        assert_eq!(
+            by_position("split", params).unwrap(),
+            Params::Array(vec![json!("k"), json!("r"), json!(5), Value::Null])
+        );
+    }
+
+    #[test]
+    fn positional_params_and_other_methods_are_left_alone() {
+        let positional = Params::Array(vec![json!("k"), json!("r"), json!(5)]);
+        assert_eq!(
+            by_position("split", positional.clone()).unwrap(),
+            positional
+        );
+
+        let other = named(json!({ "foo": 1 }));
+        assert_eq!(by_position("rpc.discover", other.clone()).unwrap(), other);
+    }
+
+    #[test]
+    fn missing_and_unknown_params_are_rejected() {
+        let params = named(json!({ "private_key": "k", "amount": null, "foo": 1 }));
+        let error = by_position("split", params).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidParams);
+        let data = error.data.unwrap();
+        assert_eq!(data["missing"], json!(["record", "amount"]));
+        assert_eq!(data["unknown"], json!(["foo"]));
+        assert_eq!(
+            data["expected"],
+            json!(["private_key", "record", "amount", "query"])
+        );
+    }
+}
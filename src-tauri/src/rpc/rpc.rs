@@ -1,38 +1,77 @@
 use std::{collections::HashMap, sync::Arc};
 
-use jsonrpc_core::{IoHandler, Result};
+use anyhow::Context;
+use chrono::Utc;
+use jsonrpc_core::{MetaIoHandler, Result};
 use jsonrpc_derive::rpc;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
-use crate::{config::Config, tls};
+use super::{
+    errors,
+    middleware::{ENVELOPE_VERSIONS, TRANSPORTS},
+    params::NamedParams,
+    types::{CostOutput, CostResult, TransactionOutput, TransactionResult},
+};
+use crate::{
+    config::{consts::NETWORKS, Config, Locked},
+    metrics, redact,
+    service::job,
+    stats, tls,
+    worker::{self, protocol::BackendCall, JobCancelled},
+};
 
 macro_rules! call_aleo_function {
-    ($func:ident($($arg:expr),*)) => {
+    ($call:expr) => {
         {
+            let call: BackendCall = $call;
+            let method = call.method();
+            let job = job::start_job(method);
+            let span = tracing::info_span!(
+                target: "aleosdk",
+                "backend_call",
+                method,
+                job_id = job.id(),
+                duration_ms = tracing::field::Empty
+            );
+            let _entered = span.enter();
             let start_time = Instant::now();
-            log::info!(target: "aleosdk","executing method '{}'",stringify!($func));
-            let result  = aleowrap::$func($($arg),*);
+            tracing::info!(target: "aleosdk","executing method '{}'",method);
+            // panics are caught, or only take down the worker process
+            let result = worker::run(job.id(), call);
             let elapsed_time = Instant::now() - start_time;
-            log::info!(target: "aleosdk","method '{}' took {} ms", stringify!($func),elapsed_time.as_millis());
+            span.record("duration_ms", elapsed_time.as_millis() as u64);
+            tracing::info!(target: "aleosdk","method '{}' took {} ms", method,elapsed_time.as_millis());
+            metrics::record_duration(method, elapsed_time);
+            let error = result.as_ref().err().map(|e| redact::redact_str(&format!("{:#}", e)).into_owned());
+            stats::record(method, elapsed_time, error.clone());
+            match &result {
+                Err(e) if e.is::<JobCancelled>() => job.cancelled(),
+                _ => job.finish(error),
+            }
             result
         }
     };
 }
 
+/// longest discovery nonce accepted, in hex characters
+const MAX_NONCE_LEN: usize = 128;
+
 lazy_static! {
-    pub static ref RPC_HANDER: Arc<IoHandler> = Arc::new(init_rpc_hander());
+    pub static ref RPC_HANDER: Arc<MetaIoHandler<(), NamedParams>> = Arc::new(init_rpc_hander());
 }
 
-pub fn init_rpc_hander() -> IoHandler {
-    let mut io = jsonrpc_core::IoHandler::new();
+pub fn init_rpc_hander() -> MetaIoHandler<(), NamedParams> {
+    let mut io = MetaIoHandler::with_middleware(NamedParams);
     io.extend_with(super::rpc::RpcImpl.to_delegate());
+    io.add_sync_method("rpc.discover", |_| Ok(super::openrpc::document().clone()));
     io
 }
 
 #[rpc]
 pub trait Rpc {
+    /// Deploys a program and returns the transaction.
     #[rpc(name = "deploy")]
     fn deploy(
         &self,
@@ -42,8 +81,9 @@ pub trait Rpc {
         imports: Option<HashMap<String, String>>,
         priority_fee_in_microcredits: Option<u64>,
         query: Option<String>,
-    ) -> Result<String>;
+    ) -> Result<TransactionOutput>;
 
+    /// Executes a program function and returns the transaction.
     #[rpc(name = "execute")]
     fn execute(
         &self,
@@ -54,8 +94,9 @@ pub trait Rpc {
         record: Option<String>,
         fee: Option<u64>,
         query: Option<String>,
-    ) -> Result<String>;
+    ) -> Result<TransactionOutput>;
 
+    /// Transfers credits and returns the transaction.
     #[rpc(name = "transfer")]
     fn transfer(
         &self,
@@ -67,8 +108,9 @@ pub trait Rpc {
         fee_record: Option<String>,
         fee: Option<u64>,
         query: Option<String>,
-    ) -> Result<String>;
+    ) -> Result<TransactionOutput>;
 
+    /// Joins two records and returns the transaction.
     #[rpc(name = "join")]
     fn join(
         &self,
@@ -78,8 +120,9 @@ pub trait Rpc {
         fee_record: Option<String>,
         fee: Option<u64>,
         query: Option<String>,
-    ) -> Result<String>;
+    ) -> Result<TransactionOutput>;
 
+    /// Splits a record and returns the transaction.
     #[rpc(name = "split")]
     fn split(
         &self,
@@ -87,15 +130,17 @@ pub trait Rpc {
         record: String,
         amount: u64,
         query: Option<String>,
-    ) -> Result<String>;
+    ) -> Result<TransactionOutput>;
 
+    /// Estimated cost of deploying a program.
     #[rpc(name = "deployment_cost")]
     fn deployment_cost(
         &self,
         program: String,
         imports: Option<HashMap<String, String>>,
-    ) -> Result<String>;
+    ) -> Result<CostOutput>;
 
+    /// Estimated cost of executing a program function.
     #[rpc(name = "execution_cost")]
     fn execution_cost(
         &self,
@@ -104,17 +149,54 @@ pub trait Rpc {
         function: String,
         inputs: Vec<String>,
         query: Option<String>,
-    ) -> Result<String>;
+    ) -> Result<CostOutput>;
 
+    /// Server version, supported methods and public key, signed together with
+    /// the client's hex `nonce`.
     #[rpc(name = "discovery")]
-    fn discovery(&self) -> Result<Discovery>;
+    fn discovery(&self, nonce: Option<String>) -> Result<Discovery>;
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Discovery {
     version: String,
+    /// methods served by the rpc handler
     features: Vec<String>,
-    pubkey: String,
+    /// hex encoded P-256 public key, none while locked
+    pubkey: Option<String>,
+    capabilities: Capabilities,
+    /// none while locked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<DiscoverySignature>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiscoverySignature {
+    /// json of the result without `signature`, clients should read it from here
+    payload: String,
+    /// unix time in seconds
+    timestamp: i64,
+    /// challenge sent by the client, empty without one
+    nonce: String,
+    /// hex encoded P-256 signature of `payload`, `timestamp` and `nonce`
+    signature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Capabilities {
+    /// request encryption schemes the server accepts
+    envelope_versions: Vec<u32>,
+    transports: Vec<String>,
+    networks: Vec<String>,
+    backend_version: BackendVersion,
+    /// the password has not been entered, no request can be decrypted
+    locked: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BackendVersion {
+    aleowrap: String,
+    snarkvm: String,
 }
 
 pub struct RpcImpl;
@@ -128,16 +210,17 @@ impl Rpc for RpcImpl {
         imports: Option<HashMap<String, String>>,
         priority_fee_in_microcredits: Option<u64>,
         query: Option<String>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'deploy'");
-        call_aleo_function!(deploy(
-            &private_key,
-            &program,
-            fee_record.as_deref(),
+    ) -> Result<TransactionOutput> {
+        let _span = enter_method("deploy");
+        call_aleo_function!(BackendCall::Deploy {
+            private_key,
+            program,
+            fee_record,
             imports,
             priority_fee_in_microcredits,
-            query.as_deref()
-        ))
+            query
+        })
+        .and_then(transaction_output)
         .to_jsonrpc_result()
         .log_rpc_error("deploy")
     }
@@ -151,17 +234,18 @@ impl Rpc for RpcImpl {
         record: Option<String>,
         fee: Option<u64>,
         query: Option<String>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'execute'");
-        call_aleo_function!(execute(
-            &private_key,
-            &program_id,
-            &function,
+    ) -> Result<TransactionOutput> {
+        let _span = enter_method("execute");
+        call_aleo_function!(BackendCall::Execute {
+            private_key,
+            program_id,
+            function,
             inputs,
-            record.as_deref(),
+            record,
             fee,
-            query.as_deref()
-        ))
+            query
+        })
+        .and_then(transaction_output)
         .to_jsonrpc_result()
         .log_rpc_error("execute")
     }
@@ -176,18 +260,19 @@ impl Rpc for RpcImpl {
         fee_record: Option<String>,
         fee: Option<u64>,
         query: Option<String>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'transfer'");
-        call_aleo_function!(transfer(
-            &private_key,
-            &recipient,
+    ) -> Result<TransactionOutput> {
+        let _span = enter_method("transfer");
+        call_aleo_function!(BackendCall::Transfer {
+            private_key,
+            recipient,
             amount,
-            &function,
-            input_record.as_deref(),
-            fee_record.as_deref(),
+            function,
+            input_record,
+            fee_record,
             fee,
-            query.as_deref()
-        ))
+            query
+        })
+        .and_then(transaction_output)
         .to_jsonrpc_result()
         .log_rpc_error("transfer")
     }
@@ -200,16 +285,17 @@ impl Rpc for RpcImpl {
         fee_record: Option<String>,
         fee: Option<u64>,
         query: Option<String>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'join'");
-        call_aleo_function!(join(
-            &private_key,
-            &first_record,
-            &second_record,
-            fee_record.as_deref(),
+    ) -> Result<TransactionOutput> {
+        let _span = enter_method("join");
+        call_aleo_function!(BackendCall::Join {
+            private_key,
+            first_record,
+            second_record,
+            fee_record,
             fee,
-            query.as_deref()
-        ))
+            query
+        })
+        .and_then(transaction_output)
         .to_jsonrpc_result()
         .log_rpc_error("join")
     }
@@ -220,22 +306,29 @@ impl Rpc for RpcImpl {
         record: String,
         amount: u64,
         query: Option<String>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'split'");
-        call_aleo_function!(split(&private_key, &record, amount, query.as_deref()))
-            .to_jsonrpc_result()
-            .log_rpc_error("split")
+    ) -> Result<TransactionOutput> {
+        let _span = enter_method("split");
+        call_aleo_function!(BackendCall::Split {
+            private_key,
+            record,
+            amount,
+            query
+        })
+        .and_then(transaction_output)
+        .to_jsonrpc_result()
+        .log_rpc_error("split")
     }
 
     fn deployment_cost(
         &self,
         program: String,
         imports: Option<HashMap<String, String>>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'deployment_cost'");
-        call_aleo_function!(deployment_cost(&program, imports))
+    ) -> Result<CostOutput> {
+        let _span = enter_method("deployment_cost");
+        call_aleo_function!(BackendCall::DeploymentCost { program, imports })
+            .and_then(|v| cost_output(v, CostResult::deployment))
             .to_jsonrpc_result()
-            .log_rpc_error("split")
+            .log_rpc_error("deployment_cost")
     }
 
     fn execution_cost(
@@ -245,43 +338,119 @@ impl Rpc for RpcImpl {
         function: String,
         inputs: Vec<String>,
         query: Option<String>,
-    ) -> Result<String> {
-        log::info!(target: "rpc","executing rpc method 'execution_cost'");
-        call_aleo_function!(execution_cost(
-            &private_key,
-            &program_id,
-            &function,
+    ) -> Result<CostOutput> {
+        let _span = enter_method("execution_cost");
+        call_aleo_function!(BackendCall::ExecutionCost {
+            private_key,
+            program_id,
+            function,
             inputs,
-            query.as_deref()
-        ))
+            query
+        })
+        .and_then(|v| cost_output(v, CostResult::execution))
         .to_jsonrpc_result()
-        .log_rpc_error("split")
+        .log_rpc_error("execution_cost")
     }
 
-    fn discovery(&self) -> Result<Discovery> {
-        log::info!(target: "rpc","executing rpc method 'discovery'");
-        let client_secret = Config::get_config().get_secret_key().to_jsonrpc_result()?;
-        Ok(Discovery {
+    fn discovery(&self, nonce: Option<String>) -> Result<Discovery> {
+        let _span = enter_method("discovery");
+        let nonce = nonce.unwrap_or_default();
+        if nonce.len() > MAX_NONCE_LEN || !nonce.chars().all(|v| v.is_ascii_hexdigit()) {
+            return Err(jsonrpc_core::Error::invalid_params(format!(
+                "nonce must be hex, at most {} characters",
+                MAX_NONCE_LEN
+            )));
+        }
+        let secret = match Config::get_config().get_secret_key() {
+            Ok(v) => Some(v),
+            Err(e) if e.is::<Locked>() => None,
+            Err(e) => return Err(to_jsonrpc_error(e)),
+        };
+        let pubkey = secret.as_ref().map(|v| hex::encode(tls::get_p256_pubkey(v)));
+        let mut features: Vec<String> = RPC_HANDER.iter().map(|(name, _)| name.clone()).collect();
+        features.sort();
+        let mut discovery = Discovery {
             version: env!("CARGO_PKG_VERSION").to_string(),
-            features: vec![
-                "deploy".to_string(),
-                "execute".to_string(),
-                "transfer".to_string(),
-                "join".to_string(),
-                "split".to_string(),
-                "deployment_cost".to_string(),
-                "execution_cost".to_string(),
-            ],
-            pubkey: hex::encode(tls::get_p256_pubkey(&client_secret)),
-        })
+            capabilities: Capabilities {
+                envelope_versions: ENVELOPE_VERSIONS.to_vec(),
+                transports: TRANSPORTS.iter().map(|v| v.to_string()).collect(),
+                networks: NETWORKS.iter().map(|v| v.to_string()).collect(),
+                backend_version: BackendVersion {
+                    aleowrap: env!("ALEOWRAP_VERSION").to_string(),
+                    snarkvm: env!("SNARKVM_VERSION").to_string(),
+                },
+                locked: pubkey.is_none(),
+            },
+            features,
+            pubkey,
+            signature: None,
+        };
+        if let Some(secret) = secret {
+            let signature = sign_discovery(&discovery, &secret, nonce).to_jsonrpc_result()?;
+            discovery.signature = Some(signature);
+        }
+        Ok(discovery)
+    }
+}
+
+/// Signs `discovery` with the server key, so clients can check it came from
+/// the key in the url and is not replayed.
+fn sign_discovery(
+    discovery: &Discovery,
+    secret: &[u8],
+    nonce: String,
+) -> anyhow::Result<DiscoverySignature> {
+    let payload = serde_json::to_string(discovery)?;
+    let timestamp = Utc::now().timestamp();
+    let message = tls::discovery_message(timestamp, &nonce, &payload);
+    Ok(DiscoverySignature {
+        signature: hex::encode(tls::p256_sign(secret, &message)?),
+        payload,
+        timestamp,
+        nonce,
+    })
+}
+
+tokio::task_local! {
+    /// Set for the calls of a request that asked for `Result-Format: legacy`.
+    pub static LEGACY_RESULTS: bool;
+}
+
+fn legacy_results() -> bool {
+    LEGACY_RESULTS.try_with(|v| *v).unwrap_or(false)
+}
+
+/// Typed transaction, or the raw string for requests that asked for legacy
+/// results. Otherwise a transaction that cant be parsed is an error with the
+/// raw transaction in the detail, never a string in its place.
+fn transaction_output(transaction: String) -> anyhow::Result<TransactionOutput> {
+    if legacy_results() {
+        return Ok(TransactionOutput::Legacy(transaction));
+    }
+    TransactionResult::parse(&transaction)
+        .map(TransactionOutput::Typed)
+        .with_context(|| format!("unexpected transaction from the backend: {}", transaction))
+}
+
+fn cost_output(
+    cost: String,
+    parse: fn(&str) -> anyhow::Result<CostResult>,
+) -> anyhow::Result<CostOutput> {
+    if legacy_results() {
+        return Ok(CostOutput::Legacy(cost));
     }
+    parse(&cost).map(CostOutput::Typed)
+}
+
+/// Enters the span of an rpc method, it ends when the guard is dropped.
+fn enter_method(method: &'static str) -> tracing::span::EnteredSpan {
+    let span = tracing::info_span!(target: "rpc", "rpc_method", method).entered();
+    tracing::info!(target: "rpc", "executing rpc method '{}'", method);
+    span
 }
 
 pub fn to_jsonrpc_error(err: anyhow::Error) -> jsonrpc_core::error::Error {
-    let mut error = jsonrpc_core::error::Error::new(jsonrpc_core::ErrorCode::ServerError(500));
-    error.data = Some(serde_json::Value::String(format!("{:#?}", err)));
-    error.message = err.to_string();
-    error
+    errors::from_anyhow(&err)
 }
 
 trait ToJsonRpcResult<T> {
@@ -308,14 +477,31 @@ impl<T> RpcLog<T> for jsonrpc_core::Result<T> {
     fn log_rpc_error(self, method: &str) -> jsonrpc_core::Result<T> {
         if self.is_err() {
             let err = self.as_ref().err().unwrap().clone();
-            log::error!(target: "rpc error", "method: {} ,code:{}, msg: {}",method, err.code.description(), err.message);
+            tracing::error!(target: "rpc error", "method: {} ,code:{}, msg: {}",method, err.code.description(), err.message);
 
-            if let Some(value) = err.data {
-                if value.is_string() {
-                    log::error!(target: "rpc error","{}",value.as_str().unwrap())
-                }
+            if let Some(detail) = err.data.as_ref().and_then(|v| v["detail"].as_str()) {
+                tracing::error!(target: "rpc error", "{}", detail)
             }
         }
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_are_typed_unless_the_request_asks_for_legacy() {
+        let typed = cost_output("[30, [20, 10]]".to_string(), CostResult::execution).unwrap();
+        assert!(matches!(typed, CostOutput::Typed(v) if v.total == 30));
+        assert!(transaction_output("abc".to_string()).is_err());
+
+        LEGACY_RESULTS.sync_scope(true, || {
+            let legacy = cost_output("2500".to_string(), CostResult::execution).unwrap();
+            assert!(matches!(legacy, CostOutput::Legacy(v) if v == "2500"));
+            let legacy = transaction_output("abc".to_string()).unwrap();
+            assert!(matches!(legacy, TransactionOutput::Legacy(v) if v == "abc"));
+        });
+    }
+}
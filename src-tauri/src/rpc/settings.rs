@@ -0,0 +1,99 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::RwLock,
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{consts::RPC_PORT, Config};
+
+const RPC_SETTINGS_KEY: &str = "rpc_settings";
+const RPC_ADDRESS_KEY: &str = "rpc_address";
+
+lazy_static! {
+    static ref SETTINGS: RwLock<RpcSettings> = RwLock::new(RpcSettings::default());
+}
+
+/// Bind settings apply on the next start of the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RpcSettings {
+    pub bind_address: IpAddr,
+    /// 0 picks a free port on every start
+    pub port: u16,
+    /// bind a free port when `port` is taken, the server url changes with it
+    pub port_fallback: bool,
+}
+
+impl Default for RpcSettings {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: RPC_PORT,
+            port_fallback: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn get_rpc_settings(&self) -> Result<RpcSettings> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(RPC_SETTINGS_KEY)? {
+            Some(v) => serde_json::from_slice(&v).context("cant parse rpc settings"),
+            None => Ok(RpcSettings::default()),
+        }
+    }
+
+    pub fn set_rpc_settings(&self, settings: &RpcSettings) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(RPC_SETTINGS_KEY, &serde_json::to_vec(settings)?)
+    }
+
+    /// Address a running server is bound to, published for the cli and
+    /// other processes of this data dir.
+    pub fn get_rpc_address(&self) -> Result<Option<SocketAddr>> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(RPC_ADDRESS_KEY)? {
+            Some(v) => Ok(Some(
+                serde_json::from_slice(&v).context("cant parse rpc address")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Publishes the bound address, `None` removes it.
+    pub fn set_rpc_address(&self, address: Option<SocketAddr>) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        match address {
+            Some(v) => db.put(RPC_ADDRESS_KEY, &serde_json::to_vec(&v)?),
+            None => db.delete(RPC_ADDRESS_KEY),
+        }
+    }
+}
+
+pub fn init() {
+    match Config::get_config().get_rpc_settings() {
+        Ok(settings) => *SETTINGS.write().unwrap() = settings,
+        Err(e) => log::warn!("failed to load rpc settings: {:#}", e),
+    }
+}
+
+pub fn settings() -> RpcSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn get_rpc_settings() -> RpcSettings {
+    settings()
+}
+
+#[tauri::command]
+pub fn set_rpc_settings(settings: RpcSettings) -> Result<(), String> {
+    Config::get_config()
+        .set_rpc_settings(&settings)
+        .map_err(|e| e.to_string())?;
+    *SETTINGS.write().unwrap() = settings;
+    Ok(())
+}
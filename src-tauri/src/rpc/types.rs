@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Result of the methods that create a transaction.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TransactionOutput {
+    Typed(TransactionResult),
+    /// the transaction json as a string, for `Result-Format: legacy`
+    Legacy(String),
+}
+
+/// Result of the cost estimates.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CostOutput {
+    Typed(CostResult),
+    /// the total as a string, for `Result-Format: legacy`
+    Legacy(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionResult {
+    /// `at1...`
+    pub transaction_id: String,
+    /// program that was deployed or called
+    pub program: String,
+    /// function called, none for deployments
+    pub function: Option<String>,
+    /// base and priority fee in microcredits, none without a fee
+    pub fee: Option<u64>,
+    pub transaction: Value,
+}
+
+/// Cost in microcredits. Parts that dont apply to the kind of transaction are
+/// left out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CostResult {
+    pub total: u64,
+    pub storage: Option<u64>,
+    pub namespace: Option<u64>,
+    pub synthesis: Option<u64>,
+    pub finalize: Option<u64>,
+}
+
+impl TransactionResult {
+    /// Reads the summary out of the transaction json returned by the backend.
+    pub fn parse(transaction: &str) -> Result<Self> {
+        let transaction: Value =
+            serde_json::from_str(transaction).context("cant parse transaction")?;
+        let transaction_id = transaction["id"]
+            .as_str()
+            .context("transaction has no id")?
+            .to_string();
+
+        let (program, function) = match transaction["type"].as_str() {
+            Some("deploy") => {
+                let source = transaction["deployment"]["program"]
+                    .as_str()
+                    .context("deployment has no program")?;
+                (program_id(source)?, None)
+            }
+            _ => {
+                // the call made by the client comes last, after the calls it made
+                let transition = transaction["execution"]["transitions"]
+                    .as_array()
+                    .and_then(|v| v.last())
+                    .context("execution has no transitions")?;
+                (
+                    transition["program"]
+                        .as_str()
+                        .context("transition has no program")?
+                        .to_string(),
+                    transition["function"].as_str().map(str::to_string),
+                )
+            }
+        };
+
+        Ok(Self {
+            transaction_id,
+            program,
+            function,
+            fee: fee_paid(&transaction),
+            transaction,
+        })
+    }
+}
+
+/// `hello.aleo` out of the program source.
+fn program_id(source: &str) -> Result<String> {
+    source
+        .split_whitespace()
+        .skip_while(|v| *v != "program")
+        .nth(1)
+        .map(|v| v.trim_end_matches(';').to_string())
+        .context("cant find program id")
+}
+
+/// Sum of the public u64 inputs of the fee transition, its base and priority fee.
+fn fee_paid(transaction: &Value) -> Option<u64> {
+    let inputs = transaction["fee"]["transition"]["inputs"].as_array()?;
+    let fees = inputs
+        .iter()
+        .filter(|v| v["type"] == "public")
+        .filter_map(|v| {
+            v["value"]
+                .as_str()?
+                .strip_suffix("u64")?
+                .parse::<u64>()
+                .ok()
+        })
+        .collect::<Vec<u64>>();
+    // no fee inputs means the fee is unknown, not zero
+    (!fees.is_empty()).then(|| fees.iter().sum())
+}
+
+impl CostResult {
+    /// `[total, [storage, synthesis, namespace]]`, or just the total.
+    pub fn deployment(cost: &str) -> Result<Self> {
+        let (total, parts) = parse_cost(cost)?;
+        Ok(Self {
+            total,
+            storage: parts.first().copied(),
+            synthesis: parts.get(1).copied(),
+            namespace: parts.get(2).copied(),
+            finalize: None,
+        })
+    }
+
+    /// `[total, [storage, finalize]]`, or just the total.
+    pub fn execution(cost: &str) -> Result<Self> {
+        let (total, parts) = parse_cost(cost)?;
+        Ok(Self {
+            total,
+            storage: parts.first().copied(),
+            namespace: None,
+            synthesis: None,
+            finalize: parts.get(1).copied(),
+        })
+    }
+}
+
+fn parse_cost(cost: &str) -> Result<(u64, Vec<u64>)> {
+    let value: Value = serde_json::from_str(cost.trim())
+        .or_else(|_| serde_json::from_str(cost.trim().trim_end_matches("u64")))
+        .with_context(|| format!("unexpected cost {}", cost))?;
+    let as_u64 = |v: &Value| {
+        v.as_u64()
+            .or_else(|| v.as_str()?.trim_end_matches("u64").parse().ok())
+    };
+    match &value {
+        Value::Array(items) => {
+            let total = items.first().and_then(as_u64);
+            let parts = items
+                .get(1)
+                .and_then(Value::as_array)
+                .map(|v| v.iter().map(as_u64).collect::<Option<Vec<u64>>>());
+            match (total, parts) {
+                (Some(total), Some(Some(parts))) => Ok((total, parts)),
+                (Some(total), None) => Ok((total, Vec::new())),
+                _ => Err(anyhow!("unexpected cost {}", cost)),
+            }
+        }
+        v => as_u64(v)
+            .map(|total| (total, Vec::new()))
+            .with_context(|| format!("unexpected cost {}", cost)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn execution(fee_inputs: Value) -> String {
+        json!({
+            "type": "execute",
+            "id": "at1abc",
+            "execution": {
+                "transitions": [
+                    { "program": "credits.aleo", "function": "transfer_public" },
+                    { "program": "hello.aleo", "function": "main" },
+                ],
+            },
+            "fee": { "transition": { "inputs": fee_inputs } },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn execution_names_the_last_transition_and_sums_the_fee() {
+        let fee_inputs = json!([
+            { "type": "public", "value": "1000u64" },
+            { "type": "public", "value": "200u64" },
+            { "type": "private", "value": "ciphertext1abc" },
+        ]);
+        let result = TransactionResult::parse(&execution(fee_inputs)).unwrap();
+        assert_eq!(result.transaction_id, "at1abc");
+        assert_eq!(result.program, "hello.aleo");
+        assert_eq!(result.function.as_deref(), Some("main"));
+        assert_eq!(result.fee, Some(1200));
+    }
+
+    #[test]
+    fn fee_without_public_inputs_is_unknown() {
+        let fee_inputs = json!([{ "type": "private", "value": "ciphertext1abc" }]);
+        let result = TransactionResult::parse(&execution(fee_inputs)).unwrap();
+        assert_eq!(result.fee, None);
+    }
+
+    #[test]
+    fn deployment_reads_the_program_id() {
+        let transaction = json!({
+            "type": "deploy",
+            "id": "at1def",
+            "deployment": { "program": "import credits.aleo;\nprogram hello.aleo;\n" },
+        });
+        let result = TransactionResult::parse(&transaction.to_string()).unwrap();
+        assert_eq!(result.program, "hello.aleo");
+        assert_eq!(result.function, None);
+        assert_eq!(result.fee, None);
+    }
+
+    #[test]
+    fn invalid_transactions_are_errors() {
+        assert!(TransactionResult::parse("at1abc").is_err());
+        assert!(TransactionResult::parse(r#"{"type": "execute"}"#).is_err());
+    }
+
+    #[test]
+    fn costs_parse_with_and_without_parts() {
+        assert_eq!(
+            parse_cost("[1500, [1000, 400, 100]]").unwrap(),
+            (1500, vec![1000, 400, 100])
+        );
+        assert_eq!(
+            parse_cost(r#"["30u64", ["20u64", "10u64"]]"#).unwrap(),
+            (30, vec![20, 10])
+        );
+        assert_eq!(parse_cost("2500u64").unwrap(), (2500, vec![]));
+        assert_eq!(parse_cost(" 2500 ").unwrap(), (2500, vec![]));
+        assert!(parse_cost("[1500, [1000, \"x\"]]").is_err());
+        assert!(parse_cost("abc").is_err());
+    }
+
+    #[test]
+    fn cost_parts_are_named_by_kind() {
+        let deployment = CostResult::deployment("[1500, [1000, 400, 100]]").unwrap();
+        assert_eq!(deployment.storage, Some(1000));
+        assert_eq!(deployment.synthesis, Some(400));
+        assert_eq!(deployment.namespace, Some(100));
+        assert_eq!(deployment.finalize, None);
+
+        let execution = CostResult::execution("[30, [20, 10]]").unwrap();
+        assert_eq!(execution.total, 30);
+        assert_eq!(execution.storage, Some(20));
+        assert_eq!(execution.finalize, Some(10));
+        assert_eq!(execution.synthesis, None);
+    }
+}
@@ -5,10 +5,7 @@ use tauri::{api::dialog, Manager};
 
 use lazy_static::lazy_static;
 
-use crate::{
-    config::{consts, Config},
-    tls,
-};
+use crate::{config::Config, rpc, tls};
 
 lazy_static! {
     pub static ref APP_HANDLE: Arc<Mutex<Option<tauri::AppHandle>>> = Arc::new(Mutex::new(None));
@@ -22,25 +19,29 @@ pub fn get_app_handle() -> tauri::AppHandle {
 
 #[tauri::command]
 pub fn get_server_url() -> Result<String, String> {
-    let fingerprint = match get_server_fingerprint() {
-        Ok(v) => v,
+    match server_url() {
+        Ok(v) => Ok(v),
         Err(e) => {
-            let err_msg = format!("failed to get server fingerprint {:#?}", e);
-            dialog::message(
-                get_app_handle().get_window("main").as_ref(),
+            error_dialog(
                 "init error",
-                err_msg,
+                format!("failed to get server fingerprint {:#?}", e),
             );
-            return Err(e.to_string());
+            Err(e.to_string())
         }
-    };
+    }
+}
+
+/// Shows `message` in a dialog, when running with a window.
+pub fn error_dialog(title: &str, message: String) {
+    if APP_HANDLE.lock().unwrap().is_some() {
+        dialog::message(get_app_handle().get_window("main").as_ref(), title, message);
+    }
+}
+
+pub fn server_url() -> Result<String> {
+    let fingerprint = get_server_fingerprint()?;
 
-    let url = format!(
-        "http://{}@{}:{}",
-        fingerprint,
-        "127.0.0.1",
-        consts::RPC_PORT
-    );
+    let url = format!("http://{}@{}", fingerprint, rpc::server_address());
 
     Ok(url)
 }
@@ -0,0 +1,205 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::Utc;
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+/// finished jobs kept for the history
+const MAX_HISTORY: usize = 200;
+
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+/// jobs started and not dropped yet, the history may have evicted some
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static CURRENT_JOB: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+lazy_static! {
+    static ref JOBS: Mutex<VecDeque<JobSummary>> = Mutex::new(VecDeque::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSummary {
+    pub id: String,
+    pub method: String,
+    /// unix time in milliseconds
+    pub started_at: i64,
+    pub duration_ms: Option<u64>,
+    pub status: JobStatus,
+    pub error: Option<String>,
+}
+
+/// Unique id for one rpc request, e.g. `job-1698000000000-7`.
+pub fn new_job_id() -> String {
+    let n = NEXT_JOB.fetch_add(1, Ordering::Relaxed);
+    format!("job-{}-{}", Utc::now().timestamp_millis(), n)
+}
+
+/// Job the current thread is working on, attached to its log records.
+pub fn current_job_id() -> Option<String> {
+    CURRENT_JOB.with(|job| job.borrow().clone())
+}
+
+/// Makes `id` the current job of this thread until the scope is dropped.
+pub fn enter_job(id: &str) -> JobScope {
+    let previous = CURRENT_JOB.with(|job| job.replace(Some(id.to_string())));
+    JobScope { previous }
+}
+
+/// Registers a running job for `method` and makes it the current one of this
+/// thread until the guard is finished or dropped.
+pub fn start_job(method: &str) -> JobGuard {
+    let id = new_job_id();
+    IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.push_back(JobSummary {
+            id: id.clone(),
+            method: method.to_string(),
+            started_at: Utc::now().timestamp_millis(),
+            duration_ms: None,
+            status: JobStatus::Running,
+            error: None,
+        });
+        while jobs.len() > MAX_HISTORY {
+            // never evict running jobs, they are still being updated
+            match jobs.iter().position(|v| v.status != JobStatus::Running) {
+                Some(i) => {
+                    jobs.remove(i);
+                }
+                None => break,
+            }
+        }
+    }
+    let scope = enter_job(&id);
+    JobGuard {
+        id,
+        finished: false,
+        _scope: scope,
+    }
+}
+
+/// The job that is running, if it is the only one. Output of the backend is
+/// not tied to a thread, so it can only be attributed while one job runs.
+pub fn active_job_id() -> Option<String> {
+    let jobs = JOBS.lock().unwrap();
+    let mut running = jobs.iter().filter(|v| v.status == JobStatus::Running);
+    match (running.next(), running.next()) {
+        (Some(job), None) => Some(job.id.clone()),
+        _ => None,
+    }
+}
+
+/// Number of jobs that have not finished yet.
+pub fn running_jobs() -> usize {
+    IN_FLIGHT.load(Ordering::Relaxed)
+}
+
+/// Marks the current job of this thread failed, from the panic hook. Gives up
+/// when the jobs are locked, the panic may have hit while this thread held them.
+pub fn fail_current_job(error: &str) {
+    let id = match current_job_id() {
+        Some(v) => v,
+        None => return,
+    };
+    if let Ok(mut jobs) = JOBS.try_lock() {
+        set_status(&mut jobs, &id, JobStatus::Failed, Some(error.to_string()));
+    }
+}
+
+/// Latest jobs first.
+pub fn job_history() -> Vec<JobSummary> {
+    let jobs = JOBS.lock().unwrap();
+    jobs.iter().rev().cloned().collect()
+}
+
+fn update_job(id: &str, status: JobStatus, error: Option<String>) {
+    set_status(&mut JOBS.lock().unwrap(), id, status, error);
+}
+
+fn set_status(jobs: &mut VecDeque<JobSummary>, id: &str, status: JobStatus, error: Option<String>) {
+    if let Some(job) = jobs.iter_mut().rev().find(|v| v.id == id) {
+        job.duration_ms = Some((Utc::now().timestamp_millis() - job.started_at).max(0) as u64);
+        job.status = status;
+        job.error = error;
+    }
+}
+
+pub struct JobScope {
+    previous: Option<String>,
+}
+
+impl Drop for JobScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT_JOB.with(|job| *job.borrow_mut() = previous);
+    }
+}
+
+pub struct JobGuard {
+    id: String,
+    finished: bool,
+    _scope: JobScope,
+}
+
+impl JobGuard {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Records the outcome, `error` is `None` on success.
+    pub fn finish(mut self, error: Option<String>) {
+        let status = match error {
+            Some(_) => JobStatus::Failed,
+            None => JobStatus::Succeeded,
+        };
+        update_job(&self.id, status, error);
+        self.finished = true;
+    }
+
+    pub fn cancelled(mut self) {
+        update_job(&self.id, JobStatus::Cancelled, None);
+        self.finished = true;
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+        if self.finished {
+            return;
+        }
+        // a panic may already have recorded why the job ended
+        let running = JOBS
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .any(|v| v.id == self.id && v.status == JobStatus::Running);
+        if running {
+            update_job(&self.id, JobStatus::Failed, Some("job aborted".to_string()));
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_job_history() -> Vec<JobSummary> {
+    job_history()
+}
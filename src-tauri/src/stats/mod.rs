@@ -0,0 +1,241 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+const STATS_PREFIX: &str = "stats/";
+/// samples older than this are deleted
+const RETENTION: Duration = Duration::from_secs(30 * 24 * 3600);
+/// expired samples deleted per recorded one, keeps recording cheap
+const PRUNE_BATCH: usize = 16;
+const MAX_SAMPLES: usize = 20_000;
+/// last hour, day and week
+const DEFAULT_WINDOWS: [u64; 3] = [3600, 24 * 3600, 7 * 24 * 3600];
+
+static NEXT_SAMPLE: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref SAMPLES: Mutex<VecDeque<Sample>> = Mutex::new(VecDeque::new());
+}
+
+/// One finished backend call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// unix time in milliseconds
+    pub timestamp: i64,
+    pub method: String,
+    pub duration_ms: u64,
+    /// redacted error, `None` on success
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MethodStats {
+    pub method: String,
+    pub count: usize,
+    /// between 0 and 1
+    pub success_rate: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowStats {
+    pub window_secs: u64,
+    pub methods: Vec<MethodStats>,
+}
+
+fn sample_key(sample: &Sample) -> String {
+    // the counter keeps keys unique within the same millisecond
+    let n = NEXT_SAMPLE.fetch_add(1, Ordering::Relaxed);
+    format!("{}{:020}-{:010}", STATS_PREFIX, sample.timestamp, n)
+}
+
+fn sample_timestamp(key: &str) -> Option<i64> {
+    key.strip_prefix(STATS_PREFIX)?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+impl Config {
+    pub fn append_stats_sample(&self, sample: &Sample) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(&sample_key(sample), &serde_json::to_vec(sample)?)?;
+        self.prune_stats_samples(sample.timestamp)
+    }
+
+    /// Deletes up to `PRUNE_BATCH` samples past the retention period. Keys
+    /// sort by time, so the expired ones come first.
+    fn prune_stats_samples(&self, now: i64) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        let cutoff = now - RETENTION.as_millis() as i64;
+        for (key, _) in db.scan_from(STATS_PREFIX, STATS_PREFIX, PRUNE_BATCH)? {
+            if sample_timestamp(&key).is_some_and(|v| v >= cutoff) {
+                break;
+            }
+            db.delete(&key)?;
+        }
+        Ok(())
+    }
+
+    /// Loads the samples within the retention period and deletes older ones.
+    pub fn load_stats_samples(&self) -> Result<Vec<Sample>> {
+        let db = self.db.clone().context("cant get db")?;
+        let cutoff = Utc::now().timestamp_millis() - RETENTION.as_millis() as i64;
+        let mut samples = Vec::new();
+        for (key, value) in db.scan_prefix(STATS_PREFIX)? {
+            if sample_timestamp(&key).is_none_or(|v| v < cutoff) {
+                db.delete(&key)?;
+                continue;
+            }
+            match serde_json::from_slice(&value) {
+                Ok(v) => samples.push(v),
+                Err(e) => log::warn!("skipping unreadable stats sample {}: {}", key, e),
+            }
+        }
+        Ok(samples)
+    }
+}
+
+/// Records a finished backend call, `error` is already redacted.
+pub fn record(method: &str, duration: Duration, error: Option<String>) {
+    let sample = Sample {
+        timestamp: Utc::now().timestamp_millis(),
+        method: method.to_string(),
+        duration_ms: duration.as_millis() as u64,
+        error,
+    };
+    if let Err(e) = Config::get_config().append_stats_sample(&sample) {
+        log::warn!("failed to persist stats sample: {:#}", e);
+    }
+    let mut samples = SAMPLES.lock().unwrap();
+    samples.push_back(sample);
+    while samples.len() > MAX_SAMPLES {
+        samples.pop_front();
+    }
+}
+
+/// Value at `quantile` of sorted `values`, nearest rank.
+fn percentile(values: &[u64], quantile: f64) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let rank = (quantile * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+fn window_stats(samples: &VecDeque<Sample>, window_secs: u64, now: i64) -> WindowStats {
+    let since = now - (window_secs as i64).saturating_mul(1000);
+    let mut by_method: BTreeMap<&str, Vec<&Sample>> = BTreeMap::new();
+    for sample in samples.iter().filter(|v| v.timestamp >= since) {
+        by_method.entry(&sample.method).or_default().push(sample);
+    }
+
+    let methods = by_method
+        .into_iter()
+        .map(|(method, samples)| {
+            let mut durations: Vec<u64> = samples.iter().map(|v| v.duration_ms).collect();
+            durations.sort_unstable();
+            let succeeded = samples.iter().filter(|v| v.error.is_none()).count();
+            let last_error = samples.iter().rev().find(|v| v.error.is_some());
+            MethodStats {
+                method: method.to_string(),
+                count: samples.len(),
+                success_rate: succeeded as f64 / samples.len() as f64,
+                p50_ms: percentile(&durations, 0.5),
+                p95_ms: percentile(&durations, 0.95),
+                max_ms: durations.last().copied().unwrap_or(0),
+                last_error: last_error.and_then(|v| v.error.clone()),
+                last_error_at: last_error.map(|v| v.timestamp),
+            }
+        })
+        .collect();
+    WindowStats {
+        window_secs,
+        methods,
+    }
+}
+
+pub fn init() {
+    match Config::get_config().load_stats_samples() {
+        Ok(loaded) => {
+            let mut samples = SAMPLES.lock().unwrap();
+            let skip = loaded.len().saturating_sub(MAX_SAMPLES);
+            *samples = loaded.into_iter().skip(skip).collect();
+        }
+        Err(e) => log::warn!("failed to load stats: {:#}", e),
+    }
+}
+
+/// Per method statistics for each window, in seconds. Defaults to the last
+/// hour, day and week.
+#[tauri::command]
+pub fn get_stats(windows: Option<Vec<u64>>) -> Vec<WindowStats> {
+    let windows = windows.unwrap_or_else(|| DEFAULT_WINDOWS.to_vec());
+    let now = Utc::now().timestamp_millis();
+    let samples = SAMPLES.lock().unwrap();
+    windows
+        .into_iter()
+        .map(|v| window_stats(&samples, v, now))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: i64) -> Sample {
+        Sample {
+            timestamp,
+            method: "deploy".to_string(),
+            duration_ms: 10,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn recording_prunes_expired_samples() {
+        let config = Config::in_memory();
+        let now = Utc::now().timestamp_millis();
+        let expired = now - RETENTION.as_millis() as i64 - 1;
+        config.append_stats_sample(&sample(expired)).unwrap();
+        config.append_stats_sample(&sample(expired - 1)).unwrap();
+        config.append_stats_sample(&sample(now)).unwrap();
+
+        let db = config.db.clone().unwrap();
+        let kept: Vec<Option<i64>> = db
+            .scan_prefix(STATS_PREFIX)
+            .unwrap()
+            .iter()
+            .map(|(key, _)| sample_timestamp(key))
+            .collect();
+        assert_eq!(kept, [Some(now)]);
+    }
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        let values: Vec<u64> = (1..=10).collect();
+        assert_eq!(percentile(&values, 0.5), 5);
+        assert_eq!(percentile(&values, 0.9), 9);
+        assert_eq!(percentile(&values, 0.99), 10);
+        assert_eq!(percentile(&values, 0.0), 1);
+        assert_eq!(percentile(&[7], 0.5), 7);
+        assert_eq!(percentile(&[], 0.5), 0);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::HashMap, env, sync::Mutex};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use serde::{Deserialize, Serialize};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::config::{consts::APP_NAME, Config};
+
+const TRACING_SETTINGS_KEY: &str = "tracing_settings";
+/// standard otel variables, a worker exports with them and has no config
+const ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const SERVICE_NAME_ENV: &str = "OTEL_SERVICE_NAME";
+/// w3c trace context of the span a worker runs in
+const TRACEPARENT_ENV: &str = "TRACEPARENT";
+const TRACEPARENT_KEY: &str = "traceparent";
+
+lazy_static! {
+    /// settings of the running exporter, `None` when not exporting
+    static ref EXPORTING: Mutex<Option<TracingSettings>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingSettings {
+    /// export spans to an OTLP collector
+    pub otlp_enabled: bool,
+    /// grpc endpoint of the collector
+    pub otlp_endpoint: String,
+    pub service_name: String,
+}
+
+impl Default for TracingSettings {
+    fn default() -> Self {
+        Self {
+            otlp_enabled: false,
+            otlp_endpoint: "http://127.0.0.1:4317".to_string(),
+            service_name: APP_NAME.to_string(),
+        }
+    }
+}
+
+impl Config {
+    pub fn get_tracing_settings(&self) -> Result<TracingSettings> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(TRACING_SETTINGS_KEY)? {
+            Some(v) => serde_json::from_slice(&v).context("cant parse tracing settings"),
+            None => Ok(TracingSettings::default()),
+        }
+    }
+
+    pub fn set_tracing_settings(&self, settings: &TracingSettings) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(TRACING_SETTINGS_KEY, &serde_json::to_vec(settings)?)
+    }
+}
+
+/// Installs the OTLP exporter when enabled. Spans and events also reach the
+/// log without it, through the `log` bridge of `tracing`.
+pub fn init() {
+    let settings = match Config::get_config().get_tracing_settings() {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("failed to load tracing settings: {:#}", e);
+            return;
+        }
+    };
+    if !settings.otlp_enabled {
+        return;
+    }
+    match install_otlp(&settings) {
+        Ok(()) => log::info!("exporting traces to {}", settings.otlp_endpoint),
+        Err(e) => log::error!("failed to start otlp exporter: {:#}", e),
+    }
+}
+
+/// Variables for a worker process, so it exports its spans as children of
+/// the current span. Empty when not exporting.
+pub fn worker_env() -> Vec<(&'static str, String)> {
+    let settings = match EXPORTING.lock().unwrap().clone() {
+        Some(v) => v,
+        None => return Vec::new(),
+    };
+    let mut carrier = HashMap::new();
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|v| v.inject_context(&context, &mut carrier));
+    let mut vars = vec![
+        (ENDPOINT_ENV, settings.otlp_endpoint),
+        (SERVICE_NAME_ENV, settings.service_name),
+    ];
+    if let Some(traceparent) = carrier.remove(TRACEPARENT_KEY) {
+        vars.push((TRACEPARENT_ENV, traceparent));
+    }
+    vars
+}
+
+/// Installs the exporter in a worker from the variables of `worker_env` and
+/// returns a span for the call, parented to the span of the server. Without
+/// them the span goes nowhere.
+pub fn init_worker(method: &str) -> tracing::Span {
+    let otlp_endpoint = match env::var(ENDPOINT_ENV) {
+        Ok(v) if !v.is_empty() => v,
+        _ => return tracing::Span::none(),
+    };
+    let settings = TracingSettings {
+        otlp_enabled: true,
+        otlp_endpoint,
+        service_name: env::var(SERVICE_NAME_ENV).unwrap_or_else(|_| APP_NAME.to_string()),
+    };
+    if let Err(e) = install_otlp(&settings) {
+        eprintln!("failed to start otlp exporter: {:#}", e);
+        return tracing::Span::none();
+    }
+    let carrier: HashMap<String, String> = env::var(TRACEPARENT_ENV)
+        .map(|v| HashMap::from([(TRACEPARENT_KEY.to_string(), v)]))
+        .unwrap_or_default();
+    let parent = global::get_text_map_propagator(|v| v.extract(&carrier));
+    let span = tracing::info_span!(target: "aleosdk", "worker", method);
+    span.set_parent(parent);
+    span
+}
+
+fn install_otlp(settings: &TracingSettings) -> Result<()> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(&settings.otlp_endpoint);
+    let resource = Resource::new(vec![KeyValue::new(
+        "service.name",
+        settings.service_name.clone(),
+    )]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(runtime::Tokio)
+        .context("cant build otlp pipeline")?;
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .context("cant install tracing subscriber")?;
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    *EXPORTING.lock().unwrap() = Some(settings.clone());
+    Ok(())
+}
+
+/// Flushes spans that have not been exported yet.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[tauri::command]
+pub fn get_tracing_settings() -> Result<TracingSettings, String> {
+    Config::get_config()
+        .get_tracing_settings()
+        .map_err(|e| e.to_string())
+}
+
+/// Takes effect on the next start.
+#[tauri::command]
+pub fn set_tracing_settings(settings: TracingSettings) -> Result<(), String> {
+    Config::get_config()
+        .set_tracing_settings(&settings)
+        .map_err(|e| e.to_string())
+}
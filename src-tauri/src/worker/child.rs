@@ -0,0 +1,81 @@
+use std::{
+    fs::File,
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+    thread::{self, ThreadId},
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+
+use super::{
+    protocol::{self, BackendResult, JobRequest},
+    BackendError,
+};
+use crate::{
+    logger::{capture, try_lock_if_panicking},
+    telemetry,
+};
+
+lazy_static! {
+    /// the real stdout, taken by the first answer
+    static ref OUTPUT: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// Entry point of `aleo-acc-service worker`: reads one call from stdin,
+/// answers on stdout and exits. Backend output goes to stderr, where the
+/// parent logs it.
+pub fn run() -> Result<()> {
+    *OUTPUT.lock().unwrap() = Some(capture::take_stdout()?);
+    install_panic_hook(thread::current().id());
+    let mut input = std::io::stdin().lock();
+
+    let request: JobRequest = protocol::read_frame(&mut input)?.context("no call received")?;
+    let span = telemetry::init_worker(request.call.method());
+    let entered = span.entered();
+    let result = match panic::catch_unwind(AssertUnwindSafe(|| request.run())) {
+        Ok(Ok(v)) => BackendResult::Ok(v),
+        Ok(Err(e)) => BackendResult::Error(BackendError::new(&e)),
+        Err(payload) => BackendResult::Panic(capture::payload_message(&*payload)),
+    };
+    // closes the span, so it is exported below
+    drop(entered);
+    // the process exits right after answering, export what is left first
+    telemetry::shutdown();
+    answer(&result)
+}
+
+/// Writes `result` unless the job was answered already.
+fn answer(result: &BackendResult) -> Result<()> {
+    let output = try_lock_if_panicking(&OUTPUT).and_then(|mut v| v.take());
+    match output {
+        Some(mut output) => protocol::write_frame(&mut output, result),
+        None => Ok(()),
+    }
+}
+
+/// Answers with the panic, wherever the backend panics. A panic of the
+/// calling thread unwinds on to `run`, on other threads the job cant finish
+/// and the worker exits.
+fn install_panic_hook(main: ThreadId) {
+    panic::set_hook(Box::new(move |info| {
+        let message = capture::panic_message(info);
+        let location = info
+            .location()
+            .map(|v| format!("{}:{}", v.file(), v.line()))
+            .unwrap_or_default();
+        let thread = thread::current();
+        eprintln!(
+            "thread '{}' panicked at {}: {}",
+            thread.name().unwrap_or("<unnamed>"),
+            location,
+            message
+        );
+        if let Err(e) = answer(&BackendResult::Panic(message)) {
+            eprintln!("cant report panic: {:#}", e);
+        }
+        if thread.id() != main {
+            std::process::exit(101);
+        }
+    }));
+}
@@ -0,0 +1,342 @@
+pub mod child;
+pub mod protocol;
+
+use std::{
+    collections::HashMap,
+    env, fmt,
+    io::{BufRead, BufReader},
+    panic::{self, AssertUnwindSafe},
+    process::{Child, ChildStderr, Command, ExitStatus, Stdio},
+    sync::{Mutex, RwLock},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Context, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{proxy, Config},
+    logger::capture,
+    redact,
+    rpc::errors::ErrorKind,
+    service::job,
+    telemetry,
+};
+
+use self::protocol::{BackendCall, BackendResult, JobRequest};
+
+const WORKER_SETTINGS_KEY: &str = "worker_settings";
+pub const TARGET_WORKER: &str = "worker";
+
+lazy_static! {
+    static ref SETTINGS: RwLock<WorkerSettings> = RwLock::new(WorkerSettings::default());
+    /// running workers by job id
+    static ref WORKERS: Mutex<HashMap<String, Worker>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerSettings {
+    /// run every backend call in its own worker process
+    pub enabled: bool,
+    /// address space limit of a worker in MiB, linux only
+    pub memory_limit_mb: Option<u64>,
+    /// cpu time limit of a worker in seconds, linux only
+    pub cpu_limit_secs: Option<u64>,
+}
+
+struct Worker {
+    child: Child,
+    cancelled: bool,
+}
+
+/// Panic caught around a backend call, with the redacted panic message.
+#[derive(Debug)]
+pub struct BackendPanic(pub String);
+
+impl BackendPanic {
+    fn new(message: &str) -> Self {
+        Self(redact::redact_str(message).into_owned())
+    }
+}
+
+impl fmt::Display for BackendPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "proving backend panicked: {}", self.0)
+    }
+}
+
+impl std::error::Error for BackendPanic {}
+
+/// The worker exited without answering, e.g. out of memory or a segfault.
+#[derive(Debug)]
+pub struct WorkerCrashed(pub String);
+
+impl fmt::Display for WorkerCrashed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "worker process crashed: {}", self.0)
+    }
+}
+
+impl std::error::Error for WorkerCrashed {}
+
+/// Error returned by the backend, classified where it happened so the kind
+/// survives the trip back from a worker.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackendError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl BackendError {
+    pub fn new(err: &anyhow::Error) -> Self {
+        Self {
+            kind: ErrorKind::classify(err),
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+#[derive(Debug)]
+pub struct JobCancelled;
+
+impl fmt::Display for JobCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "job cancelled")
+    }
+}
+
+impl std::error::Error for JobCancelled {}
+
+/// Runs `call` for `job_id`, in a worker process when enabled and otherwise
+/// on this thread with panics caught.
+pub fn run(job_id: &str, call: BackendCall) -> Result<String> {
+    let settings = SETTINGS.read().unwrap().clone();
+    match settings.enabled {
+        true => run_in_worker(job_id, call, &settings),
+        false => match panic::catch_unwind(AssertUnwindSafe(|| request(call).run())) {
+            Ok(v) => v.map_err(|e| anyhow::Error::new(BackendError::new(&e))),
+            Err(payload) => Err(anyhow::Error::new(BackendPanic::new(
+                &capture::payload_message(&*payload),
+            ))),
+        },
+    }
+}
+
+/// `call` with the proxy the backend goes through.
+fn request(call: BackendCall) -> JobRequest {
+    JobRequest {
+        call,
+        proxy: proxy::active_proxy(),
+    }
+}
+
+fn run_in_worker(job_id: &str, call: BackendCall, settings: &WorkerSettings) -> Result<String> {
+    let exe = env::current_exe().context("cant find own executable")?;
+    let mut command = Command::new(exe);
+    command
+        .arg("worker")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    command.envs(telemetry::worker_env());
+    #[cfg(target_os = "linux")]
+    apply_limits(&mut command, settings);
+    #[cfg(not(target_os = "linux"))]
+    let _ = settings;
+
+    let mut child = command.spawn().context("cant start worker process")?;
+    let mut stdin = child.stdin.take().context("worker has no stdin")?;
+    let mut stdout = child.stdout.take().context("worker has no stdout")?;
+    let stderr = child.stderr.take().context("worker has no stderr")?;
+    log::info!(target: TARGET_WORKER, "job {} runs in worker {}", job_id, child.id());
+
+    let forwarder = forward_stderr(job_id, stderr);
+    WORKERS.lock().unwrap().insert(
+        job_id.to_string(),
+        Worker {
+            child,
+            cancelled: false,
+        },
+    );
+
+    let result = protocol::write_frame(&mut stdin, &request(call)).and_then(|_| {
+        // the worker only reads one frame, closing stdin lets it notice a broken parent
+        drop(stdin);
+        protocol::read_frame::<BackendResult>(&mut stdout)
+    });
+
+    let worker = WORKERS.lock().unwrap().remove(job_id);
+    let (status, cancelled) = match worker {
+        Some(mut worker) => (worker.child.wait().ok(), worker.cancelled),
+        None => (None, false),
+    };
+    if let Some(forwarder) = forwarder {
+        let _ = forwarder.join();
+    }
+
+    if cancelled {
+        return Err(anyhow::Error::new(JobCancelled));
+    }
+    match result {
+        Ok(Some(BackendResult::Ok(v))) => Ok(v),
+        Ok(Some(BackendResult::Error(e))) => Err(anyhow::Error::new(e)),
+        Ok(Some(BackendResult::Panic(message))) => {
+            Err(anyhow::Error::new(BackendPanic::new(&message)))
+        }
+        Ok(None) => Err(anyhow::Error::new(WorkerCrashed(describe_exit(status)))),
+        Err(e) => Err(anyhow::Error::new(WorkerCrashed(format!(
+            "{}, {:#}",
+            describe_exit(status),
+            e
+        )))),
+    }
+}
+
+/// Logs what the worker and the backend in it print, tagged with the job.
+fn forward_stderr(job_id: &str, stderr: ChildStderr) -> Option<JoinHandle<()>> {
+    let job_id = job_id.to_string();
+    let spawned = thread::Builder::new()
+        .name(format!("worker {}", job_id))
+        .spawn(move || {
+            let _scope = job::enter_job(&job_id);
+            let mut reader = BufReader::new(stderr);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let text = String::from_utf8_lossy(&line);
+                        let text = text.trim_end_matches(['\n', '\r']);
+                        if !text.is_empty() {
+                            log::info!(target: TARGET_WORKER, "{}", text);
+                        }
+                    }
+                }
+            }
+        });
+    match spawned {
+        Ok(v) => Some(v),
+        Err(e) => {
+            log::error!("cant start worker output thread: {}", e);
+            None
+        }
+    }
+}
+
+fn describe_exit(status: Option<ExitStatus>) -> String {
+    let status = match status {
+        Some(v) => v,
+        None => return "exit status unknown".to_string(),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return match signal {
+                libc::SIGKILL => "killed, likely out of memory".to_string(),
+                libc::SIGXCPU => "cpu time limit exceeded".to_string(),
+                libc::SIGSEGV => "segmentation fault".to_string(),
+                libc::SIGABRT => "aborted, possibly out of memory".to_string(),
+                v => format!("killed by signal {}", v),
+            };
+        }
+    }
+    status.to_string()
+}
+
+#[cfg(target_os = "linux")]
+fn apply_limits(command: &mut Command, settings: &WorkerSettings) {
+    use std::os::unix::process::CommandExt;
+
+    let memory = settings
+        .memory_limit_mb
+        .map(|v| v.saturating_mul(1024 * 1024));
+    let cpu = settings.cpu_limit_secs;
+    if memory.is_none() && cpu.is_none() {
+        return;
+    }
+    let limit = |value: u64| libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+    // SAFETY: only async-signal-safe setrlimit calls between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(bytes) = memory {
+                if libc::setrlimit(libc::RLIMIT_AS, &limit(bytes)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(secs) = cpu {
+                if libc::setrlimit(libc::RLIMIT_CPU, &limit(secs)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Kills the worker running `job_id`. Jobs running in process cant be cancelled.
+pub fn cancel(job_id: &str) -> Result<()> {
+    let mut workers = WORKERS.lock().unwrap();
+    let worker = workers
+        .get_mut(job_id)
+        .ok_or_else(|| anyhow!("job {} is not running in a worker", job_id))?;
+    worker.cancelled = true;
+    worker.child.kill().context("cant kill worker")?;
+    log::info!(target: TARGET_WORKER, "job {} cancelled", job_id);
+    Ok(())
+}
+
+impl Config {
+    pub fn get_worker_settings(&self) -> Result<WorkerSettings> {
+        let db = self.db.clone().context("cant get db")?;
+        match db.get(WORKER_SETTINGS_KEY)? {
+            Some(v) => serde_json::from_slice(&v).context("cant parse worker settings"),
+            None => Ok(WorkerSettings::default()),
+        }
+    }
+
+    pub fn set_worker_settings(&self, settings: &WorkerSettings) -> Result<()> {
+        let db = self.db.clone().context("cant get db")?;
+        db.put(WORKER_SETTINGS_KEY, &serde_json::to_vec(settings)?)
+    }
+}
+
+pub fn init() {
+    match Config::get_config().get_worker_settings() {
+        Ok(settings) => *SETTINGS.write().unwrap() = settings,
+        Err(e) => log::warn!("failed to load worker settings: {:#}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_worker_settings() -> WorkerSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn set_worker_settings(settings: WorkerSettings) -> Result<(), String> {
+    Config::get_config()
+        .set_worker_settings(&settings)
+        .map_err(|e| e.to_string())?;
+    *SETTINGS.write().unwrap() = settings;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_job(job_id: String) -> Result<(), String> {
+    cancel(&job_id).map_err(|e| e.to_string())
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::BackendError;
+use crate::config::proxy::{self, ProxyConfig};
+
+/// upper bound for one frame, programs and transactions stay far below it
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// What the server sends a worker. The proxy travels with the call since the
+/// worker has no config of its own.
+#[derive(Serialize, Deserialize)]
+pub struct JobRequest {
+    pub call: BackendCall,
+    pub proxy: Option<ProxyConfig>,
+}
+
+impl JobRequest {
+    /// Runs the call on this thread, with an http client going through the
+    /// proxy.
+    pub fn run(self) -> Result<String> {
+        let client = proxy::build_http_client(self.proxy.as_ref())?;
+        self.call.run(&client)
+    }
+}
+
+/// One backend call.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum BackendCall {
+    Deploy {
+        private_key: String,
+        program: String,
+        fee_record: Option<String>,
+        imports: Option<HashMap<String, String>>,
+        priority_fee_in_microcredits: Option<u64>,
+        query: Option<String>,
+    },
+    Execute {
+        private_key: String,
+        program_id: String,
+        function: String,
+        inputs: Vec<String>,
+        record: Option<String>,
+        fee: Option<u64>,
+        query: Option<String>,
+    },
+    Transfer {
+        private_key: String,
+        recipient: String,
+        amount: u64,
+        function: String,
+        input_record: Option<String>,
+        fee_record: Option<String>,
+        fee: Option<u64>,
+        query: Option<String>,
+    },
+    Join {
+        private_key: String,
+        first_record: String,
+        second_record: String,
+        fee_record: Option<String>,
+        fee: Option<u64>,
+        query: Option<String>,
+    },
+    Split {
+        private_key: String,
+        record: String,
+        amount: u64,
+        query: Option<String>,
+    },
+    DeploymentCost {
+        program: String,
+        imports: Option<HashMap<String, String>>,
+    },
+    ExecutionCost {
+        private_key: String,
+        program_id: String,
+        function: String,
+        inputs: Vec<String>,
+        query: Option<String>,
+    },
+}
+
+impl BackendCall {
+    pub fn method(&self) -> &'static str {
+        match self {
+            BackendCall::Deploy { .. } => "deploy",
+            BackendCall::Execute { .. } => "execute",
+            BackendCall::Transfer { .. } => "transfer",
+            BackendCall::Join { .. } => "join",
+            BackendCall::Split { .. } => "split",
+            BackendCall::DeploymentCost { .. } => "deployment_cost",
+            BackendCall::ExecutionCost { .. } => "execution_cost",
+        }
+    }
+
+    /// Runs the call on this thread, queries and broadcasts go through
+    /// `client`.
+    pub fn run(self, client: &reqwest::blocking::Client) -> Result<String> {
+        match self {
+            BackendCall::Deploy {
+                private_key,
+                program,
+                fee_record,
+                imports,
+                priority_fee_in_microcredits,
+                query,
+            } => aleowrap::deploy(
+                &private_key,
+                &program,
+                fee_record.as_deref(),
+                imports,
+                priority_fee_in_microcredits,
+                query.as_deref(),
+                client,
+            ),
+            BackendCall::Execute {
+                private_key,
+                program_id,
+                function,
+                inputs,
+                record,
+                fee,
+                query,
+            } => aleowrap::execute(
+                &private_key,
+                &program_id,
+                &function,
+                inputs,
+                record.as_deref(),
+                fee,
+                query.as_deref(),
+                client,
+            ),
+            BackendCall::Transfer {
+                private_key,
+                recipient,
+                amount,
+                function,
+                input_record,
+                fee_record,
+                fee,
+                query,
+            } => aleowrap::transfer(
+                &private_key,
+                &recipient,
+                amount,
+                &function,
+                input_record.as_deref(),
+                fee_record.as_deref(),
+                fee,
+                query.as_deref(),
+                client,
+            ),
+            BackendCall::Join {
+                private_key,
+                first_record,
+                second_record,
+                fee_record,
+                fee,
+                query,
+            } => aleowrap::join(
+                &private_key,
+                &first_record,
+                &second_record,
+                fee_record.as_deref(),
+                fee,
+                query.as_deref(),
+                client,
+            ),
+            BackendCall::Split {
+                private_key,
+                record,
+                amount,
+                query,
+            } => aleowrap::split(&private_key, &record, amount, query.as_deref(), client),
+            BackendCall::DeploymentCost { program, imports } => {
+                aleowrap::deployment_cost(&program, imports)
+            }
+            BackendCall::ExecutionCost {
+                private_key,
+                program_id,
+                function,
+                inputs,
+                query,
+            } => aleowrap::execution_cost(
+                &private_key,
+                &program_id,
+                &function,
+                inputs,
+                query.as_deref(),
+                client,
+            ),
+        }
+    }
+}
+
+/// Answer of a worker, it exits after sending it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", content = "value", rename_all = "snake_case")]
+pub enum BackendResult {
+    Ok(String),
+    Error(BackendError),
+    /// message of a panic caught in the worker
+    Panic(String),
+}
+
+/// Writes `value` as a 4 byte big endian length followed by its json.
+pub fn write_frame<T: Serialize>(writer: &mut impl Write, value: &T) -> Result<()> {
+    let body = serde_json::to_vec(value)?;
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(anyhow!("frame of {} bytes is too large", body.len()));
+    }
+    writer.write_all(&(body.len() as u32).to_be_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads one frame, `None` if the stream ended before it started.
+pub fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> Result<Option<T>> {
+    let mut len = [0u8; 4];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(anyhow!("truncated frame header")),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e).context("cant read frame header"),
+        }
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(anyhow!("frame of {} bytes is too large", len));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).context("truncated frame")?;
+    Ok(Some(
+        serde_json::from_slice(&body).context("cant parse frame")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(len: u32, body: &[u8]) -> Vec<u8> {
+        let mut frame = len.to_be_bytes().to_vec();
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn frames_round_trip_until_the_stream_ends() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, &BackendResult::Ok("at1xyz".to_string())).unwrap();
+        write_frame(&mut stream, &BackendResult::Panic("boom".to_string())).unwrap();
+
+        let mut reader = stream.as_slice();
+        let first = read_frame::<BackendResult>(&mut reader).unwrap();
+        assert!(matches!(first, Some(BackendResult::Ok(v)) if v == "at1xyz"));
+        let second = read_frame::<BackendResult>(&mut reader).unwrap();
+        assert!(matches!(second, Some(BackendResult::Panic(v)) if v == "boom"));
+        assert!(read_frame::<BackendResult>(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let too_large = "x".repeat(MAX_FRAME_SIZE);
+        let mut stream = Vec::new();
+        assert!(write_frame(&mut stream, &too_large).is_err());
+        assert!(stream.is_empty());
+
+        let header = frame(MAX_FRAME_SIZE as u32 + 1, b"");
+        let err = read_frame::<String>(&mut header.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        let body = frame(10, b"\"abc");
+        let err = read_frame::<String>(&mut body.as_slice()).unwrap_err();
+        assert_eq!(err.to_string(), "truncated frame");
+
+        let header = &frame(10, b"")[..2];
+        let err = read_frame::<String>(&mut &header[..]).unwrap_err();
+        assert_eq!(err.to_string(), "truncated frame header");
+    }
+}
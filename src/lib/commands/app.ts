@@ -19,4 +19,45 @@ export interface BuildInfo {
 
 export async function get_build_info(): Promise<BuildInfo> {
     return await invoke('get_build_info', {})
-}
\ No newline at end of file
+}
+
+export interface JobSummary {
+    id: string,
+    method: string,
+    started_at: number,
+    duration_ms: number | null,
+    status: "running" | "succeeded" | "failed" | "cancelled",
+    error: string | null,
+}
+
+export async function get_job_history(): Promise<JobSummary[]> {
+    return await invoke('get_job_history', {})
+}
+
+export async function cancel_job(job_id: string) {
+    await invoke('cancel_job', { jobId: job_id })
+}
+
+export async function export_diagnostics(path: string) {
+    await invoke('export_diagnostics', { path: path })
+}
+
+export interface MethodStats {
+    method: string,
+    count: number,
+    success_rate: number,
+    p50_ms: number,
+    p95_ms: number,
+    max_ms: number,
+    last_error: string | null,
+    last_error_at: number | null,
+}
+
+export interface WindowStats {
+    window_secs: number,
+    methods: MethodStats[],
+}
+
+export async function get_stats(windows?: number[]): Promise<WindowStats[]> {
+    return await invoke('get_stats', { windows: windows ?? null })
+}
@@ -0,0 +1,34 @@
+import { invoke } from "@tauri-apps/api/tauri";
+
+export interface AuditEntry {
+    seq: number,
+    timestamp: number,
+    client: string,
+    method: string,
+    params: any,
+    outcome: "success" | "error",
+    error: string | null,
+    duration_ms: number,
+    transaction_id: string | null,
+    prev_hash: string,
+    hash: string,
+}
+
+export interface AuditVerification {
+    valid: boolean,
+    entries: number,
+    broken_at: number | null,
+    error: string | null,
+}
+
+export async function get_audit_log(after?: number, limit?: number): Promise<AuditEntry[]> {
+    return await invoke('get_audit_log', { after: after ?? null, limit: limit ?? null })
+}
+
+export async function verify_audit_log(): Promise<AuditVerification> {
+    return await invoke('verify_audit_log', {})
+}
+
+export async function export_audit_log(path: string): Promise<number> {
+    return await invoke('export_audit_log', { path: path })
+}
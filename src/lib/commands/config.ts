@@ -1,9 +1,107 @@
 import { invoke } from "@tauri-apps/api/tauri";
 
-export async function set_proxy(proxy: String) {
+export type ProxyScheme = "http" | "https" | "socks5";
+
+export interface ProxyConfig {
+    scheme: ProxyScheme,
+    host: string,
+    port: number,
+    username?: string | null,
+    password?: string | null,
+    no_proxy: string[],
+}
+
+export interface ProxyTestResult {
+    ok: boolean,
+    url: string,
+    status: number | null,
+    elapsed_ms: number,
+    error: string | null,
+}
+
+export async function set_proxy(proxy: ProxyConfig | null) {
     return await invoke('set_proxy', { proxy: proxy })
 }
 
-export async function get_proxy(): Promise<string | null> {
+export async function get_proxy(): Promise<ProxyConfig | null> {
     return await invoke('get_proxy', {})
-}
\ No newline at end of file
+}
+
+export async function test_proxy(proxy: ProxyConfig | null, url?: string): Promise<ProxyTestResult> {
+    return await invoke('test_proxy', { proxy: proxy, url: url ?? null })
+}
+
+export function proxy_to_string(proxy: ProxyConfig | null): string {
+    if (!proxy) {
+        return "";
+    }
+    let user = proxy.username ? `${proxy.username}@` : "";
+    return `${proxy.scheme}://${user}${proxy.host}:${proxy.port}`;
+}
+
+export interface RedactionSettings {
+    fields: string[],
+}
+
+export async function get_redaction_settings(): Promise<RedactionSettings> {
+    return await invoke('get_redaction_settings', {})
+}
+
+export async function set_redaction_settings(settings: RedactionSettings) {
+    await invoke('set_redaction_settings', { settings: settings })
+}
+
+
+export interface WorkerSettings {
+    enabled: boolean,
+    memory_limit_mb: number | null,
+    cpu_limit_secs: number | null,
+}
+
+export async function get_worker_settings(): Promise<WorkerSettings> {
+    return await invoke('get_worker_settings', {})
+}
+
+export async function set_worker_settings(settings: WorkerSettings) {
+    await invoke('set_worker_settings', { settings: settings })
+}
+
+export interface MetricsSettings {
+    enabled: boolean,
+}
+
+export async function get_metrics_settings(): Promise<MetricsSettings> {
+    return await invoke('get_metrics_settings', {})
+}
+
+export async function set_metrics_settings(settings: MetricsSettings) {
+    await invoke('set_metrics_settings', { settings: settings })
+}
+
+export interface TracingSettings {
+    otlp_enabled: boolean,
+    otlp_endpoint: string,
+    service_name: string,
+}
+
+export async function get_tracing_settings(): Promise<TracingSettings> {
+    return await invoke('get_tracing_settings', {})
+}
+
+export async function set_tracing_settings(settings: TracingSettings) {
+    await invoke('set_tracing_settings', { settings: settings })
+}
+
+export interface RpcSettings {
+    bind_address: string,
+    port: number,
+    port_fallback: boolean,
+}
+
+export async function get_rpc_settings(): Promise<RpcSettings> {
+    return await invoke('get_rpc_settings', {})
+}
+
+export async function set_rpc_settings(settings: RpcSettings) {
+    await invoke('set_rpc_settings', { settings: settings })
+}
@@ -0,0 +1,72 @@
+import { invoke } from "@tauri-apps/api/tauri";
+
+export type LevelFilter = "OFF" | "ERROR" | "WARN" | "INFO" | "DEBUG" | "TRACE";
+
+export interface LogSettings {
+    level: LevelFilter,
+    targets: { [target: string]: LevelFilter },
+    max_file_size: number,
+    rotation: "never" | "hourly" | "daily",
+    retention: number,
+}
+
+export async function get_log_settings(): Promise<LogSettings> {
+    return await invoke('get_log_settings', {})
+}
+
+export async function set_log_settings(settings: LogSettings) {
+    await invoke('set_log_settings', { settings: settings })
+}
+
+export interface LogRecord {
+    id: number,
+    timestamp: number,
+    level: "ERROR" | "WARN" | "INFO" | "DEBUG" | "TRACE",
+    target: string,
+    message: string,
+    job_id: string | null,
+}
+
+export interface LogQuery {
+    level?: LevelFilter,
+    targets?: string[],
+    job_id?: string,
+    since?: number,
+    until?: number,
+    search?: string,
+    after?: number,
+    before?: number,
+    limit?: number,
+}
+
+export interface LogPage {
+    records: LogRecord[],
+    has_more: boolean,
+    prev_cursor: number | null,
+    next_cursor: number | null,
+}
+
+export async function get_logs(query: LogQuery = {}): Promise<LogPage> {
+    return await invoke('get_logs', { query: query })
+}
+
+export function format_log(record: LogRecord): string {
+    let time = new Date(record.timestamp).toLocaleString();
+    let job = record.job_id ? ` (${record.job_id})` : "";
+    return `${time} :: ${record.level} [${record.target}]${job} - ${record.message}`;
+}
+
+export const LOG_EVENT = "log-records";
+
+export interface LogBatch {
+    records: LogRecord[],
+    dropped: number,
+}
+
+export async function subscribe_logs() {
+    await invoke('subscribe_logs', {})
+}
+
+export async function unsubscribe_logs() {
+    await invoke('unsubscribe_logs', {})
+}